@@ -0,0 +1,61 @@
+//! Lets the autopilot play rounds of the game without a window or GPU and prints the score of
+//! each round.
+//!
+//! `cargo run --example headless -- <rounds>`
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_bird::{BevyBirdPlugin, GameState, HeadlessPlugins, ScoreInfo, SimulationTick};
+
+#[derive(Resource)]
+struct Rounds {
+    remaining: u32,
+    scores: Vec<u32>,
+}
+
+fn main() -> AppExit {
+    let rounds = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(10);
+
+    App::new()
+        .add_plugins(HeadlessPlugins)
+        .add_plugins(BevyBirdPlugin::headless().with_autopilot(true))
+        // Step the simulation as fast as possible, one fixed timestep per update
+        .insert_resource(TimeUpdateStrategy::ManualDuration(
+            Time::<Fixed>::default().timestep(),
+        ))
+        .insert_resource(Rounds {
+            remaining: rounds,
+            scores: Vec::new(),
+        })
+        .add_systems(OnEnter(GameState::Dead), count_round)
+        .run()
+}
+
+fn count_round(
+    mut rounds: ResMut<Rounds>,
+    score_info: Res<ScoreInfo>,
+    tick: Res<SimulationTick>,
+    mut app_exit: MessageWriter<AppExit>,
+) {
+    println!(
+        "Round over, score: {}, died on tick {}",
+        score_info.current_score, **tick
+    );
+
+    rounds.scores.push(score_info.current_score);
+    rounds.remaining = rounds.remaining.saturating_sub(1);
+
+    if rounds.remaining == 0 {
+        let best = rounds.scores.iter().max().copied().unwrap_or_default();
+        let total: u32 = rounds.scores.iter().sum();
+        println!(
+            "Played {} rounds, best: {best}, average: {:.1}",
+            rounds.scores.len(),
+            total as f32 / rounds.scores.len() as f32
+        );
+        app_exit.write(AppExit::Success);
+    }
+}
@@ -0,0 +1,46 @@
+//! Replays a recorded run without a window or GPU and checks that it ends with the same score.
+//! Exits with an error if the replay diverges, e.g. because the high score was not legitimate.
+//!
+//! `cargo run --example verify_replay -- last_replay.ron`
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_bird::{BevyBirdPlugin, GameState, HeadlessPlugins, Replay, ScoreInfo, SimulationTick};
+
+#[derive(Resource, Deref)]
+struct Expected(Replay);
+
+fn main() -> AppExit {
+    let path = std::env::args()
+        .nth(1)
+        .expect("Usage: verify_replay <replay file>");
+    let replay = Replay::load(&path).unwrap_or_else(|err| panic!("Could not load {path}: {err}"));
+
+    App::new()
+        .add_plugins(HeadlessPlugins)
+        .add_plugins(BevyBirdPlugin::headless().with_replay_playback(replay.clone()))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(
+            Time::<Fixed>::default().timestep(),
+        ))
+        .insert_resource(Expected(replay))
+        .add_systems(OnEnter(GameState::Dead), check_result)
+        .run()
+}
+
+fn check_result(
+    expected: Res<Expected>,
+    score_info: Res<ScoreInfo>,
+    tick: Res<SimulationTick>,
+    mut app_exit: MessageWriter<AppExit>,
+) {
+    if expected.score == score_info.current_score && expected.death_tick == **tick {
+        println!("Verified: score {} on tick {}", expected.score, **tick);
+        app_exit.write(AppExit::Success);
+    } else {
+        println!(
+            "Mismatch: replay claims score {} on tick {}, but the run ended with score {} on tick {}",
+            expected.score, expected.death_tick, score_info.current_score, **tick
+        );
+        app_exit.write(AppExit::error());
+    }
+}
@@ -2,7 +2,8 @@ use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
 use bevy_kira_audio::prelude::*;
 
-use crate::gameplay::{GameState, JumpedEvent, ScoredEvent};
+use crate::gameplay::{JumpedEvent, MAX_COMBO_BONUS, PlayerDied, ScoredEvent};
+use crate::scene::pickups::PickupCollected;
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States)]
 enum AssetState {
@@ -19,6 +20,8 @@ struct AudioAssets {
     gun: Handle<AudioSource>,
     #[asset(path = "audio/hitHurt.ogg")]
     death: Handle<AudioSource>,
+    #[asset(path = "audio/highscore.ogg")]
+    pickup: Handle<AudioSource>,
 }
 
 pub struct GameAudioPlugin;
@@ -34,14 +37,20 @@ impl Plugin for GameAudioPlugin {
             )
             .add_systems(
                 Update,
-                (score_audio, jump_audio).run_if(in_state(AssetState::Loaded)),
-            )
-            .add_systems(OnEnter(GameState::Dead), death_audio);
+                (score_audio, jump_audio, death_audio, pickup_audio)
+                    .run_if(in_state(AssetState::Loaded)),
+            );
     }
 }
 
-fn death_audio(audio_assets: Res<AudioAssets>, audio: Res<Audio>) {
-    audio.play(audio_assets.death.clone());
+fn death_audio(
+    audio_assets: Res<AudioAssets>,
+    audio: Res<Audio>,
+    mut player_died: MessageReader<PlayerDied>,
+) {
+    for _ in player_died.read() {
+        audio.play(audio_assets.death.clone());
+    }
 }
 
 fn score_audio(
@@ -49,8 +58,12 @@ fn score_audio(
     audio: Res<Audio>,
     mut score_event: MessageReader<ScoredEvent>,
 ) {
-    for _ in score_event.read() {
-        audio.play(audio_assets.coin.clone());
+    for scored in score_event.read() {
+        // Higher for every step of the combo
+        let playback_rate = 1.0 + 0.1 * scored.combo.min(MAX_COMBO_BONUS) as f64;
+        audio
+            .play(audio_assets.coin.clone())
+            .with_playback_rate(playback_rate);
     }
 }
 
@@ -63,3 +76,13 @@ fn jump_audio(
         audio.play(audio_assets.gun.clone());
     }
 }
+
+fn pickup_audio(
+    audio_assets: Res<AudioAssets>,
+    audio: Res<Audio>,
+    mut pickup_collected: MessageReader<PickupCollected>,
+) {
+    for _ in pickup_collected.read() {
+        audio.play(audio_assets.pickup.clone());
+    }
+}
@@ -0,0 +1,557 @@
+//! Builds courses inside the game. Press `E` before a run to open the editor and again to play
+//! what you built.
+//!
+//! In the editor the camera pans along the course. Pipe pairs are placed, dragged and deleted with
+//! the mouse, and the gap and behaviour of the selected pair can be changed. Courses are saved to
+//! and loaded from the [`CourseFile`], in the same format `--course` plays.
+
+use std::path::PathBuf;
+
+use avian3d::math::PI;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use leafwing_input_manager::prelude::*;
+
+use crate::gameplay::{GameState, PlayArea, ResetRun};
+use crate::scene::SceneSettings;
+use crate::scene::course::{Course, CoursePipePair, FinishLine, spawn_course};
+use crate::scene::pipes::{PIPE_WIDTH, PipeBehaviour, PipeMotion, PipePair, SpawnPipePair};
+
+/// Positions and sizes are rounded to this, so saved courses stay readable
+const SNAP: f32 = 0.1;
+const PAN_SPEED: f32 = 15.0;
+const GAP_STEP: f32 = 0.1;
+const MIN_GAP: f32 = 1.5;
+const MAX_GAP: f32 = 6.0;
+/// How close to the finish line the cursor has to be to grab it
+const FINISH_LINE_REACH: f32 = 0.5;
+
+/// Where the editor saves and loads the course
+#[derive(Resource, Clone, Debug)]
+pub struct CourseFile(pub PathBuf);
+
+impl Default for CourseFile {
+    fn default() -> Self {
+        Self(PathBuf::from("./course.course.ron"))
+    }
+}
+
+#[derive(Actionlike, PartialEq, Eq, Hash, Clone, Copy, Debug, Reflect)]
+pub enum EditorAction {
+    /// Open the editor before a run, or leave it to play the course
+    Toggle,
+    /// Place a pipe pair, or grab the pair or finish line under the cursor
+    Place,
+    /// Delete the pipe pair under the cursor, or the selected one
+    Delete,
+    /// Switch the selected pair to the next [`PipeBehaviour`]
+    CycleBehaviour,
+    #[actionlike(Axis)]
+    Pan,
+    /// Widen or narrow the gap of the selected pair
+    #[actionlike(Axis)]
+    Gap,
+    Save,
+    Load,
+}
+
+fn create_input_map() -> InputMap<EditorAction> {
+    let mut input_map = InputMap::default();
+
+    input_map.insert(EditorAction::Toggle, KeyCode::KeyE);
+    input_map.insert(EditorAction::Place, MouseButton::Left);
+    input_map.insert(EditorAction::Delete, MouseButton::Right);
+    input_map.insert(EditorAction::Delete, KeyCode::Delete);
+    input_map.insert(EditorAction::Delete, KeyCode::Backspace);
+    input_map.insert(EditorAction::CycleBehaviour, KeyCode::KeyB);
+    input_map.insert_axis(EditorAction::Pan, VirtualAxis::ad());
+    input_map.insert_axis(EditorAction::Pan, VirtualAxis::horizontal_arrow_keys());
+    input_map.insert_axis(EditorAction::Gap, MouseScrollAxis::Y);
+    input_map.insert(
+        EditorAction::Save,
+        ButtonlikeChord::modified(ModifierKey::Control, KeyCode::KeyS),
+    );
+    input_map.insert(
+        EditorAction::Load,
+        ButtonlikeChord::modified(ModifierKey::Control, KeyCode::KeyO),
+    );
+
+    input_map
+}
+
+/// The pipe pair or finish line that is being edited
+#[derive(Component)]
+struct Selected;
+
+#[derive(Resource, Default)]
+struct Editing {
+    name: String,
+    /// Kept as it is, the editor can't change it
+    play_area: Option<PlayArea>,
+    /// Where the camera was before opening the editor
+    camera_home: Transform,
+    /// From the cursor to the grabbed pair or finish line, while dragging
+    drag_offset: Option<Vec2>,
+}
+
+impl Editing {
+    // Everything about the course that isn't laid out in the level
+    fn set_course(&mut self, course: &Course) {
+        self.name = course.name.clone();
+        self.play_area = course.play_area.clone();
+    }
+
+    fn course(&self, mut pipes: Vec<CoursePipePair>, finish_x: f32) -> Course {
+        pipes.sort_by(|a, b| a.x.total_cmp(&b.x));
+
+        Course {
+            name: self.name.clone(),
+            pipes,
+            finish_x,
+            play_area: self.play_area.clone(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct HelpText;
+
+pub(crate) struct EditorPlugin {
+    pub course_file: CourseFile,
+}
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(InputManagerPlugin::<EditorAction>::default())
+            .init_resource::<ActionState<EditorAction>>()
+            .insert_resource(create_input_map())
+            .insert_resource(self.course_file.clone())
+            .init_resource::<Editing>()
+            .add_systems(OnEnter(GameState::Editor), open_editor)
+            .add_systems(OnExit(GameState::Editor), close_editor)
+            .add_systems(
+                Update,
+                (
+                    enter_editor.run_if(in_state(GameState::Ready)),
+                    (
+                        pan_camera,
+                        grab_or_place,
+                        drag,
+                        delete,
+                        adjust_gap,
+                        cycle_behaviour,
+                        save_course,
+                        load_course,
+                        update_help_text,
+                        leave_editor,
+                    )
+                        .chain()
+                        .run_if(in_state(GameState::Editor)),
+                ),
+            );
+    }
+}
+
+/// Where the cursor points at on the plane the pipes move in
+#[derive(SystemParam)]
+struct CoursePointer<'w, 's> {
+    window: Single<'w, 's, &'static Window, With<PrimaryWindow>>,
+    camera: Single<'w, 's, (&'static Camera, &'static GlobalTransform)>,
+}
+
+impl CoursePointer<'_, '_> {
+    fn position(&self) -> Option<Vec2> {
+        let (camera, camera_transform) = *self.camera;
+        let cursor = self.window.cursor_position()?;
+        let ray = camera.viewport_to_world(camera_transform, cursor).ok()?;
+        let distance = ray.intersect_plane(Vec3::ZERO, InfinitePlane3d::new(Vec3::Z))?;
+
+        Some(ray.get_point(distance).truncate())
+    }
+}
+
+fn snap(value: f32) -> f32 {
+    (value / SNAP).round() * SNAP
+}
+
+fn enter_editor(
+    action_state: Res<ActionState<EditorAction>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if action_state.just_pressed(&EditorAction::Toggle) {
+        next_state.set(GameState::Editor);
+    }
+}
+
+fn open_editor(
+    mut commands: Commands,
+    mut editing: ResMut<Editing>,
+    level_query: Query<Entity, Or<(With<PipePair>, With<FinishLine>)>>,
+    camera: Single<&Transform, With<Camera3d>>,
+    course: Option<Res<Course>>,
+    course_file: Res<CourseFile>,
+) {
+    // Continue with the course being played, or the one in the file
+    let course = match course {
+        Some(course) => course.clone(),
+        None => Course::load(&course_file.0).unwrap_or_else(|_| Course {
+            name: "New course".to_string(),
+            pipes: Vec::new(),
+            finish_x: 14.0,
+            play_area: None,
+        }),
+    };
+
+    for entity in &level_query {
+        commands.entity(entity).despawn();
+    }
+    spawn_course(&mut commands, &course);
+
+    *editing = Editing {
+        camera_home: **camera,
+        ..default()
+    };
+    editing.set_course(&course);
+
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(20.0),
+            bottom: Val::Px(20.0),
+            ..default()
+        },
+        Text::default(),
+        TextFont::from_font_size(20.0),
+        HelpText,
+        DespawnOnExit(GameState::Editor),
+    ));
+}
+
+fn close_editor(editing: Res<Editing>, mut camera: Single<&mut Transform, With<Camera3d>>) {
+    **camera = editing.camera_home;
+}
+
+fn pan_camera(
+    action_state: Res<ActionState<EditorAction>>,
+    mut camera: Single<&mut Transform, With<Camera3d>>,
+    time: Res<Time>,
+) {
+    camera.translation.x += action_state.value(&EditorAction::Pan) * PAN_SPEED * time.delta_secs();
+}
+
+// The pipe pair or finish line under the cursor, pipe pairs first
+fn hovered(
+    position: Vec2,
+    pair_query: &Query<(Entity, &Transform, &PipeMotion), With<PipePair>>,
+    finish_line: &Single<(Entity, &Transform), With<FinishLine>>,
+) -> Option<Entity> {
+    pair_query
+        .iter()
+        .find(|(_, transform, _)| (transform.translation.x - position.x).abs() <= PIPE_WIDTH / 2.0)
+        .map(|(entity, _, _)| entity)
+        .or_else(|| {
+            let (entity, transform) = &**finish_line;
+            ((transform.translation.x - position.x).abs() <= FINISH_LINE_REACH).then_some(*entity)
+        })
+}
+
+fn grab_or_place(
+    mut commands: Commands,
+    action_state: Res<ActionState<EditorAction>>,
+    pointer: CoursePointer,
+    pair_query: Query<(Entity, &Transform, &PipeMotion), With<PipePair>>,
+    finish_line: Single<(Entity, &Transform), With<FinishLine>>,
+    selected_query: Query<Entity, With<Selected>>,
+    scene_settings: Res<SceneSettings>,
+    mut editing: ResMut<Editing>,
+) {
+    if !action_state.just_pressed(&EditorAction::Place) {
+        return;
+    }
+    let Some(position) = pointer.position() else {
+        return;
+    };
+
+    for selected in &selected_query {
+        commands.entity(selected).remove::<Selected>();
+    }
+
+    if let Some(entity) = hovered(position, &pair_query, &finish_line) {
+        let anchor = match pair_query.get(entity) {
+            Ok((_, transform, motion)) => Vec2::new(transform.translation.x, motion.rest_y),
+            Err(_) => finish_line.1.translation.truncate(),
+        };
+
+        commands.entity(entity).insert(Selected);
+        editing.drag_offset = Some(anchor - position);
+        return;
+    }
+
+    // Place the middle of the gap at the cursor
+    let gap_y = snap(scene_settings.pipe_gap_y);
+    let spawn = SpawnPipePair {
+        position_x: snap(position.x),
+        height: snap(position.y - gap_y / 2.0),
+        gap_y,
+        rotation: pair_query.iter().count() as f32 * PI * 0.5,
+        behaviour: PipeBehaviour::Static,
+        pickup: None,
+    };
+    editing.drag_offset = Some(Vec2::new(spawn.position_x, spawn.height) - position);
+
+    commands.queue(move |world: &mut World| {
+        let pipe_pair = spawn.spawn(world);
+        world.entity_mut(pipe_pair).insert(Selected);
+    });
+}
+
+fn drag(
+    action_state: Res<ActionState<EditorAction>>,
+    pointer: CoursePointer,
+    mut pair_query: Query<(&mut Transform, &mut PipeMotion), (With<PipePair>, With<Selected>)>,
+    mut finish_line: Query<&mut Transform, (With<FinishLine>, With<Selected>, Without<PipePair>)>,
+    mut editing: ResMut<Editing>,
+) {
+    if !action_state.pressed(&EditorAction::Place) {
+        editing.drag_offset = None;
+        return;
+    }
+    let (Some(offset), Some(position)) = (editing.drag_offset, pointer.position()) else {
+        return;
+    };
+    let target = position + offset;
+
+    for (mut transform, mut motion) in &mut pair_query {
+        transform.translation.x = snap(target.x);
+        motion.rest_y = snap(target.y);
+    }
+
+    // The finish line only moves along the course
+    for mut transform in &mut finish_line {
+        transform.translation.x = snap(target.x);
+    }
+}
+
+fn delete(
+    mut commands: Commands,
+    action_state: Res<ActionState<EditorAction>>,
+    pointer: CoursePointer,
+    pair_query: Query<(Entity, &Transform, &PipeMotion), With<PipePair>>,
+    finish_line: Single<(Entity, &Transform), With<FinishLine>>,
+    selected_query: Query<Entity, (With<Selected>, With<PipePair>)>,
+) {
+    if !action_state.just_pressed(&EditorAction::Delete) {
+        return;
+    }
+
+    // The finish line can't be deleted
+    let hovered_pair = pointer
+        .position()
+        .and_then(|position| hovered(position, &pair_query, &finish_line))
+        .filter(|entity| pair_query.contains(*entity));
+
+    if let Some(pipe_pair) = hovered_pair.or_else(|| selected_query.iter().next()) {
+        commands.entity(pipe_pair).despawn();
+    }
+}
+
+fn adjust_gap(
+    action_state: Res<ActionState<EditorAction>>,
+    mut selected_query: Query<&mut PipeMotion, With<Selected>>,
+) {
+    let steps = action_state.value(&EditorAction::Gap);
+    if steps == 0.0 {
+        return;
+    }
+
+    for mut motion in &mut selected_query {
+        motion.rest_gap_y = snap(motion.rest_gap_y + steps * GAP_STEP).clamp(MIN_GAP, MAX_GAP);
+    }
+}
+
+fn cycle_behaviour(
+    action_state: Res<ActionState<EditorAction>>,
+    mut selected_query: Query<&mut PipeMotion, With<Selected>>,
+) {
+    if !action_state.just_pressed(&EditorAction::CycleBehaviour) {
+        return;
+    }
+
+    for mut motion in &mut selected_query {
+        let index = PipeBehaviour::MOVING
+            .iter()
+            .position(|behaviour| *behaviour == motion.behaviour);
+
+        motion.behaviour = match index {
+            None => PipeBehaviour::MOVING[0],
+            Some(index) => PipeBehaviour::MOVING
+                .get(index + 1)
+                .copied()
+                .unwrap_or(PipeBehaviour::Static),
+        };
+    }
+}
+
+// The course as it is laid out in the editor
+fn edited_course(
+    editing: &Editing,
+    pair_query: &Query<(&Transform, &PipeMotion), With<PipePair>>,
+    finish_line: &Transform,
+) -> Course {
+    let pipes = pair_query
+        .iter()
+        .map(|(transform, motion)| CoursePipePair {
+            x: transform.translation.x,
+            y: motion.rest_y,
+            gap_y: motion.rest_gap_y,
+            behaviour: motion.behaviour,
+        })
+        .collect();
+
+    editing.course(pipes, finish_line.translation.x)
+}
+
+fn save_course(
+    action_state: Res<ActionState<EditorAction>>,
+    editing: Res<Editing>,
+    pair_query: Query<(&Transform, &PipeMotion), With<PipePair>>,
+    finish_line: Single<&Transform, With<FinishLine>>,
+    course_file: Res<CourseFile>,
+) {
+    if !action_state.just_pressed(&EditorAction::Save) {
+        return;
+    }
+
+    let course = edited_course(&editing, &pair_query, &finish_line);
+    match course.save(&course_file.0) {
+        Ok(()) => info!("Saved the course to {}", course_file.0.display()),
+        Err(err) => warn!("Could not save the course: {err}"),
+    }
+}
+
+fn load_course(
+    mut commands: Commands,
+    action_state: Res<ActionState<EditorAction>>,
+    mut editing: ResMut<Editing>,
+    level_query: Query<Entity, Or<(With<PipePair>, With<FinishLine>)>>,
+    course_file: Res<CourseFile>,
+) {
+    if !action_state.just_pressed(&EditorAction::Load) {
+        return;
+    }
+
+    match Course::load(&course_file.0) {
+        Ok(course) => {
+            for entity in &level_query {
+                commands.entity(entity).despawn();
+            }
+            spawn_course(&mut commands, &course);
+            editing.set_course(&course);
+            info!("Loaded the course from {}", course_file.0.display());
+        }
+        Err(err) => warn!("Could not load the course: {err}"),
+    }
+}
+
+fn update_help_text(
+    mut help_text: Single<&mut Text, With<HelpText>>,
+    editing: Res<Editing>,
+    course_file: Res<CourseFile>,
+    selected_query: Query<(&Transform, Option<&PipeMotion>), With<Selected>>,
+) {
+    let selected = match selected_query.single() {
+        Ok((transform, Some(motion))) => format!(
+            "Pipe pair at x {:.1}, height {:.1}, gap {:.1}, {:?}",
+            transform.translation.x, motion.rest_y, motion.rest_gap_y, motion.behaviour
+        ),
+        Ok((transform, None)) => format!("Finish line at x {:.1}", transform.translation.x),
+        Err(_) => "Nothing selected".to_string(),
+    };
+
+    help_text.0 = format!(
+        "{} ({})\n\
+         Click: place or drag | Right click: delete | Scroll: gap | B: behaviour\n\
+         A/D: pan | Ctrl+S: save | Ctrl+O: load | E: play\n\
+         {selected}",
+        editing.name,
+        course_file.0.display()
+    );
+}
+
+// Plays the course as it is laid out, an empty course goes back to the endless pipes
+fn leave_editor(
+    mut commands: Commands,
+    action_state: Res<ActionState<EditorAction>>,
+    editing: Res<Editing>,
+    pair_query: Query<(&Transform, &PipeMotion), With<PipePair>>,
+    finish_line: Single<&Transform, With<FinishLine>>,
+) {
+    if !action_state.just_pressed(&EditorAction::Toggle) {
+        return;
+    }
+
+    let course = edited_course(&editing, &pair_query, &finish_line);
+    if course.pipes.is_empty() {
+        commands.remove_resource::<Course>();
+    } else if let Err(err) = course.check() {
+        warn!("Can't play the course: {err}");
+        return;
+    } else {
+        commands.insert_resource(course);
+    }
+
+    commands.queue(ResetRun);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::BoundsPolicy;
+
+    fn course(name: &str, play_area: Option<PlayArea>) -> Course {
+        Course {
+            name: name.to_string(),
+            pipes: vec![CoursePipePair {
+                x: 7.0,
+                y: 0.0,
+                gap_y: 3.1,
+                behaviour: PipeBehaviour::Static,
+            }],
+            finish_x: 14.0,
+            play_area,
+        }
+    }
+
+    #[test]
+    fn load_then_save_keeps_the_play_area() {
+        let clamped = PlayArea {
+            top: 5.0,
+            top_policy: BoundsPolicy::Clamp,
+            ..default()
+        };
+        let loaded = course("Loaded", Some(clamped));
+        let path = std::env::temp_dir().join("bevy_bird_editor_round_trip.course.ron");
+        loaded.save(&path).unwrap();
+
+        // Another course with its own play area was being edited before
+        let mut editing = Editing::default();
+        editing.set_course(&course("Previous", Some(PlayArea::default())));
+        editing.set_course(&Course::load(&path).unwrap());
+
+        let saved = editing.course(loaded.pipes.clone(), loaded.finish_x);
+        saved.save(&path).unwrap();
+        assert_eq!(Course::load(&path).unwrap(), loaded);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn loading_a_course_without_play_area_drops_the_previous_one() {
+        let mut editing = Editing::default();
+        editing.set_course(&course("Previous", Some(PlayArea::default())));
+        editing.set_course(&course("Loaded", None));
+
+        assert_eq!(editing.course(Vec::new(), 14.0).play_area, None);
+    }
+}
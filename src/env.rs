@@ -0,0 +1,297 @@
+//! A reinforcement learning environment in the style of OpenAI Gym.
+//!
+//! Runs the game headless and advances it one fixed timestep per [`BirdEnv::step`].
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::gameplay::{GameState, GameplaySystems, ResetRun, ScoredEvent};
+use crate::player::Player;
+use crate::player::inputs::Action;
+use crate::scene::SeedMode;
+use crate::scene::pipes::{PIPE_WIDTH, PipePair};
+use crate::{BevyBirdPlugin, HeadlessPlugins};
+
+/// Reward for every pipe passed
+pub const SCORE_REWARD: f32 = 1.0;
+/// Reward for the step the bird dies in
+pub const DEATH_REWARD: f32 = -1.0;
+
+/// What the agent does during a step
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentAction {
+    #[default]
+    Idle,
+    /// Only has an effect if the previous step was not a jump, just like holding down the button
+    Jump,
+}
+
+/// A pipe pair ahead of the bird
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct PipeObservation {
+    /// Horizontal distance from the bird to the middle of the pipes
+    pub distance: f32,
+    /// Height of the middle of the gap
+    pub gap_height: f32,
+    /// Distance between the lower and upper pipe
+    pub gap_size: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Observation {
+    pub bird_y: f32,
+    pub bird_velocity_y: f32,
+    /// The next two pipe pairs, nearest first
+    pub pipes: [PipeObservation; 2],
+}
+
+/// The result of a single [`BirdEnv::step`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f32,
+    /// The bird died or finished the course, call [`BirdEnv::reset`] to start the next episode.
+    /// Until then every step is done as well, without advancing the game.
+    pub done: bool,
+}
+
+/// The headless game wrapped for an agent.
+///
+/// ```no_run
+/// # use bevy_bird::env::{AgentAction, BirdEnv};
+/// let mut env = BirdEnv::new();
+/// let mut observation = env.reset(42);
+///
+/// loop {
+///     let action = if observation.bird_y < observation.pipes[0].gap_height {
+///         AgentAction::Jump
+///     } else {
+///         AgentAction::Idle
+///     };
+///
+///     let step = env.step(action);
+///     if step.done {
+///         break;
+///     }
+///     observation = step.observation;
+/// }
+/// ```
+pub struct BirdEnv {
+    app: App,
+    /// The episode ended and [`BirdEnv::reset`] wasn't called since
+    done: bool,
+}
+
+/// The action to apply in the next fixed timestep
+#[derive(Resource, Default)]
+pub(crate) struct PendingAction(pub AgentAction);
+
+#[derive(Resource, Default)]
+struct StepReward(f32);
+
+#[derive(Resource, Default)]
+struct FixedTicks(u64);
+
+impl Default for BirdEnv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BirdEnv {
+    pub fn new() -> Self {
+        Self::with_plugin(BevyBirdPlugin::headless())
+    }
+
+    /// Use a differently configured game, e.g. with other [`crate::PlayerSettings`].
+    /// Rendering, audio and UI should stay disabled.
+    pub fn with_plugin(plugin: BevyBirdPlugin) -> Self {
+        let mut app = App::new();
+
+        app.add_plugins(HeadlessPlugins)
+            .add_plugins(plugin)
+            // Every update advances the simulation by exactly one fixed timestep
+            .insert_resource(TimeUpdateStrategy::ManualDuration(
+                Time::<Fixed>::default().timestep(),
+            ))
+            .add_plugins(AgentInputPlugin)
+            .init_resource::<StepReward>()
+            .init_resource::<FixedTicks>()
+            .add_systems(FixedFirst, count_fixed_ticks)
+            .add_systems(
+                FixedUpdate,
+                collect_score_rewards.after(GameplaySystems::Rules),
+            );
+
+        app.finish();
+        app.cleanup();
+
+        // Let the startup systems spawn the player and the level
+        app.update();
+
+        Self { app, done: false }
+    }
+
+    /// Starts a new episode on the pipe layout of `seed` and returns the first observation.
+    ///
+    /// The run is started right away, which counts as a jump.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        *self.app.world_mut().resource_mut::<SeedMode>() = SeedMode::Fixed(seed);
+        self.app.world_mut().commands().queue(ResetRun);
+        self.app.world_mut().flush();
+
+        // Back to `Ready` with a fresh level
+        self.tick(AgentAction::Idle);
+
+        while *self.state() != GameState::Playing {
+            self.tick(AgentAction::Jump);
+        }
+
+        self.done = false;
+        self.observe()
+    }
+
+    /// Advances the game by one fixed timestep
+    pub fn step(&mut self, action: AgentAction) -> Step {
+        // The game would go on to the next run by itself
+        if self.done {
+            return Step {
+                observation: self.observe(),
+                reward: 0.0,
+                done: true,
+            };
+        }
+
+        let was_alive = *self.state() == GameState::Playing;
+        let reward = self.tick(action);
+
+        let dying = was_alive && self.is_entering(GameState::Dead);
+        let done = dying || (was_alive && self.is_entering(GameState::Finished));
+        let reward = if dying { reward + DEATH_REWARD } else { reward };
+        self.done = done;
+
+        Step {
+            observation: self.observe(),
+            reward,
+            done,
+        }
+    }
+
+    pub fn observe(&mut self) -> Observation {
+        observe(self.app.world_mut())
+    }
+
+    /// The app running the game, for anything the environment doesn't expose
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+    fn state(&self) -> &GameState {
+        self.app.world().resource::<State<GameState>>().get()
+    }
+
+    // Death and finishing a course are only applied as a state transition at the start of the
+    // next timestep
+    fn is_entering(&self, state: GameState) -> bool {
+        *self.state() == state
+            || matches!(
+                self.app.world().resource::<NextState<GameState>>(),
+                NextState::Pending(pending) if *pending == state
+            )
+    }
+
+    // Runs updates until one fixed timestep has passed, returns the score reward of that timestep
+    fn tick(&mut self, action: AgentAction) -> f32 {
+        let world = self.app.world_mut();
+        world.resource_mut::<PendingAction>().0 = action;
+        world.resource_mut::<StepReward>().0 = 0.0;
+        let ticks = world.resource::<FixedTicks>().0;
+
+        while self.app.world().resource::<FixedTicks>().0 == ticks {
+            self.app.update();
+        }
+
+        self.app.world().resource::<StepReward>().0
+    }
+}
+
+/// Presses the jump action as told by the [`PendingAction`] instead of reading the input devices
+pub(crate) struct AgentInputPlugin;
+
+impl Plugin for AgentInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingAction>()
+            .add_systems(PostStartup, remove_player_input)
+            .add_systems(
+                FixedUpdate,
+                apply_action
+                    .after(GameplaySystems::Tick)
+                    .before(GameplaySystems::Input),
+            );
+    }
+}
+
+pub(crate) fn observe(world: &mut World) -> Observation {
+    let (bird_transform, bird_velocity) = world
+        .query_filtered::<(&Transform, &LinearVelocity), With<Player>>()
+        .single(world)
+        .unwrap();
+    let bird_position = bird_transform.translation;
+    let bird_velocity_y = bird_velocity.y;
+
+    let mut pipes: Vec<PipeObservation> = world
+        .query::<(&Transform, &PipePair)>()
+        .iter(world)
+        .map(|(transform, pipe)| PipeObservation {
+            distance: transform.translation.x - bird_position.x,
+            gap_height: transform.translation.y + pipe.gap_y / 2.0,
+            gap_size: pipe.gap_y,
+        })
+        // Pipes count as "ahead" until the bird is fully past them
+        .filter(|pipe| pipe.distance > -PIPE_WIDTH / 2.0)
+        .collect();
+    pipes.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+    Observation {
+        bird_y: bird_position.y,
+        bird_velocity_y,
+        pipes: [
+            pipes.first().copied().unwrap_or_default(),
+            pipes.get(1).copied().unwrap_or_default(),
+        ],
+    }
+}
+
+// The agent is the only source of input
+fn remove_player_input(mut commands: Commands, player: Single<Entity, With<Player>>) {
+    commands.entity(*player).remove::<InputMap<Action>>();
+}
+
+fn count_fixed_ticks(mut ticks: ResMut<FixedTicks>) {
+    ticks.0 += 1;
+}
+
+pub(crate) fn apply_action(
+    action: Res<PendingAction>,
+    mut action_state: Single<&mut ActionState<Action>, With<Player>>,
+) {
+    // Release after every press, a jump only registers when the action is newly pressed
+    if action_state.pressed(&Action::Jump) {
+        action_state.release(&Action::Jump);
+    } else if action.0 == AgentAction::Jump {
+        action_state.press(&Action::Jump);
+    }
+}
+
+fn collect_score_rewards(
+    mut scored_event: MessageReader<ScoredEvent>,
+    mut reward: ResMut<StepReward>,
+) {
+    for _ in scored_event.read() {
+        reward.0 += SCORE_REWARD;
+    }
+}
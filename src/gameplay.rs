@@ -1,35 +1,290 @@
-use crate::player::PlayerSettings;
-use crate::player::controls::{check_for_game_start, jump};
-use crate::scene::pipes::PipePair;
-use crate::scene::{SceneSettings, spawn_level};
+use crate::player::autopilot::AttractDemo;
+use crate::player::controls::{check_for_game_start, cycle_difficulty, jump};
+use crate::player::health::{Health, Invulnerable};
+use crate::player::{Player, PlayerSettings, initial_transform};
+use crate::scene::course::{Course, FinishLine};
+use crate::scene::difficulty::{Difficulty, DifficultyAssist};
+use crate::scene::pickups::{ActiveEffects, SHIELD_GRACE_SECONDS};
+use crate::scene::pipes::{GapSensor, Pipe, PipePair, UpperPipe};
+use crate::scene::seed::current_day;
+use crate::scene::{SceneSettings, SeedMode, spawn_level};
 
 use avian3d::math::Quaternion;
 use avian3d::prelude::*;
+use bevy::app::FixedMainScheduleOrder;
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy::state::state::StateTransition;
 use bevy::time::common_conditions::on_timer;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::time::Duration;
 
-#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum GameState {
     #[default]
     Ready,
     Playing,
     Dead,
+    /// The player crossed the finish line of a [`Course`]
+    Finished,
+    /// Building a course in the editor, only entered from [`GameState::Ready`]
+    Editor,
 }
 
-#[derive(Resource, Default, Copy, Clone, Deserialize, Serialize)]
+/// Whether a run is paused, only exists during [`GameState::Playing`].
+///
+/// Pausing stops the virtual clock, so no fixed timesteps run at all: the pipes, the difficulty
+/// ramp and the physics stand still, and the run carries on exactly where it left off.
+#[derive(SubStates, Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+#[source(GameState = GameState::Playing)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+#[derive(Resource, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct ScoreInfo {
+    /// Points for the pipes passed in the current run, without bonus points. The high scores,
+    /// replays and difficulty go by this score.
     pub current_score: u32,
-    pub high_score: u32,
+    /// Bonus points of the current run, for perfect passes and near misses
+    #[serde(default)]
+    pub bonus_score: u32,
+    /// Perfect passes and near misses in a row
+    #[serde(default)]
+    pub combo: u32,
+    /// High score of every difficulty
+    #[serde(default)]
+    pub high_scores: BTreeMap<Difficulty, u32>,
+    /// High score of the daily challenge of `daily_day`, for every difficulty
+    #[serde(default)]
+    pub daily_high_scores: BTreeMap<Difficulty, u32>,
+    /// Days since the Unix epoch
+    #[serde(default)]
+    pub daily_day: u64,
 }
 
-// Indicates if a pipe has passed the player
-#[derive(Component)]
-struct Scored;
+impl ScoreInfo {
+    /// The score of the current run including bonus points
+    pub fn total_score(&self) -> u32 {
+        self.current_score + self.bonus_score
+    }
 
-#[derive(Message)]
-pub struct ScoredEvent;
+    /// The high score a run in the given mode and difficulty competes with
+    pub fn high_score_for(&self, seed_mode: SeedMode, difficulty: Difficulty) -> u32 {
+        let high_scores = match seed_mode {
+            SeedMode::Daily if self.daily_day == current_day() => &self.daily_high_scores,
+            SeedMode::Daily => return 0,
+            SeedMode::Random | SeedMode::Fixed(_) => &self.high_scores,
+        };
+
+        high_scores.get(&difficulty).copied().unwrap_or_default()
+    }
+
+    fn high_score_for_mut(&mut self, seed_mode: SeedMode, difficulty: Difficulty) -> &mut u32 {
+        let high_scores = match seed_mode {
+            SeedMode::Daily => &mut self.daily_high_scores,
+            SeedMode::Random | SeedMode::Fixed(_) => &mut self.high_scores,
+        };
+
+        high_scores.entry(difficulty).or_default()
+    }
+}
+
+/// Number of fixed timesteps since the current run started.
+///
+/// Only counts while [`GameState::Playing`], so after a run it holds the tick the player died on.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq, Deref)]
+pub struct SimulationTick(pub u64);
+
+/// Order of the gameplay systems within a fixed timestep.
+///
+/// All gameplay runs in `FixedUpdate` so that a run only depends on the inputs and the RNG seed,
+/// never on the frame rate.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameplaySystems {
+    /// Advance the [`SimulationTick`]
+    Tick,
+    /// React to the jump action
+    Input,
+    /// Move and recycle the pipes
+    Movement,
+    /// Scoring and death checks
+    Rules,
+}
+
+/// What a collider is, so that only the colliders that matter to each other touch
+#[derive(PhysicsLayer, Clone, Copy, Debug, Default)]
+pub enum GameLayer {
+    /// Decorations, they don't touch the player
+    #[default]
+    Default,
+    Player,
+    /// Hurts the player on contact
+    Obstacle,
+    /// Collected by flying through it
+    Pickup,
+    /// Notices the player flying through it
+    Trigger,
+}
+
+impl GameLayer {
+    pub fn player() -> CollisionLayers {
+        CollisionLayers::new(
+            GameLayer::Player,
+            [GameLayer::Obstacle, GameLayer::Pickup, GameLayer::Trigger],
+        )
+    }
+
+    pub fn obstacle() -> CollisionLayers {
+        CollisionLayers::new(GameLayer::Obstacle, [GameLayer::Player, GameLayer::Default])
+    }
+
+    /// For pickups and triggers, which only care about the player
+    pub fn player_sensor(layer: GameLayer) -> CollisionLayers {
+        CollisionLayers::new(layer, GameLayer::Player)
+    }
+}
+
+/// What killed the bird. Hits only kill without health, shield or invulnerability left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeathCause {
+    /// Hit the [`UpperPipe`] of a pipe pair
+    UpperPipe,
+    /// Hit the lower pipe of a pipe pair
+    LowerPipe,
+    /// Hit a [`GameLayer::Obstacle`] that isn't a pipe
+    Obstacle,
+    /// Fell below the [`PlayArea`]
+    Fell,
+    /// Flew above the [`PlayArea`]
+    Ceiling,
+}
+
+impl fmt::Display for DeathCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeathCause::UpperPipe => write!(f, "hit the top pipe"),
+            DeathCause::LowerPipe => write!(f, "hit the bottom pipe"),
+            DeathCause::Obstacle => write!(f, "hit an obstacle"),
+            DeathCause::Fell => write!(f, "fell"),
+            DeathCause::Ceiling => write!(f, "hit the ceiling"),
+        }
+    }
+}
+
+/// The player died, which ends the run
+#[derive(Message, Debug, Clone, Copy, PartialEq)]
+pub struct PlayerDied {
+    pub cause: DeathCause,
+    /// Where the bird hit the obstacle, or where it left the level
+    pub position: Vec3,
+    /// Pipes passed, see [`ScoreInfo::current_score`]
+    pub score: u32,
+    /// The score including bonus points, as shown on screen
+    pub total_score: u32,
+    /// Time since the run started, in simulated seconds
+    pub seconds: f32,
+}
+
+/// What happens to the bird when it leaves the [`PlayArea`]
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BoundsPolicy {
+    /// End the run
+    #[default]
+    Kill,
+    /// Stop the bird at the edge
+    Clamp,
+    /// Move the bird to the opposite edge, keeping its velocity
+    Wrap,
+}
+
+/// How high and low the bird can fly, with a [`BoundsPolicy`] for each edge.
+///
+/// A [`Course`] can bring its own play area, which replaces this resource while it's played.
+#[derive(Resource, Reflect, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct PlayArea {
+    /// By default just below the top of the lowest upper pipe, so the bird can't fly over them
+    pub top: f32,
+    pub bottom: f32,
+    pub top_policy: BoundsPolicy,
+    pub bottom_policy: BoundsPolicy,
+}
+
+impl Default for PlayArea {
+    fn default() -> Self {
+        Self {
+            top: 9.0,
+            bottom: -20.0,
+            top_policy: BoundsPolicy::Kill,
+            bottom_policy: BoundsPolicy::Kill,
+        }
+    }
+}
+
+/// The play area of the [`Course`] being played if it has one, the [`PlayArea`] resource otherwise
+#[derive(SystemParam)]
+pub struct ActivePlayArea<'w> {
+    play_area: Res<'w, PlayArea>,
+    course: Option<Res<'w, Course>>,
+}
+
+impl ActivePlayArea<'_> {
+    pub fn get(&self) -> &PlayArea {
+        self.course
+            .as_ref()
+            .and_then(|course| course.play_area.as_ref())
+            .unwrap_or(&self.play_area)
+    }
+}
+
+/// The middle of the bird has to stay this close to the middle of a gap for a
+/// [`PassKind::Perfect`]
+pub const PERFECT_PASS_DISTANCE: f32 = 0.6;
+/// The bird has to come this close to a pipe without touching it for a [`PassKind::NearMiss`]
+pub const NEAR_MISS_DISTANCE: f32 = 0.2;
+/// The bonus for a perfect pass or near miss grows with the combo, up to this
+pub const MAX_COMBO_BONUS: u32 = 5;
+
+/// How the player flew through a gap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PassKind {
+    Plain,
+    /// Stayed close to the middle of the gap all the way through
+    Perfect,
+    /// Came close to a pipe without touching it
+    NearMiss,
+}
+
+impl PassKind {
+    fn of(sensor: &GapSensor) -> Self {
+        if (0.0..NEAR_MISS_DISTANCE).contains(&sensor.min_clearance) {
+            PassKind::NearMiss
+        } else if sensor.min_clearance.is_finite() && sensor.max_offset < PERFECT_PASS_DISTANCE {
+            PassKind::Perfect
+        } else {
+            PassKind::Plain
+        }
+    }
+}
+
+/// The player passed a pipe pair
+#[derive(Message, Debug, Clone, Copy, PartialEq)]
+pub struct ScoredEvent {
+    pub pass: PassKind,
+    /// Added to [`ScoreInfo::bonus_score`], grows with the combo. Double score adds a point.
+    pub bonus: u32,
+    /// Perfect passes and near misses in a row, including this one. 0 after a plain pass.
+    pub combo: u32,
+}
 
 #[derive(Message)]
 pub struct JumpedEvent;
@@ -38,24 +293,61 @@ pub struct StateTransitionPlugin;
 
 impl Plugin for StateTransitionPlugin {
     fn build(&self, app: &mut App) {
+        // Also apply state transitions at the start of every fixed timestep, otherwise the
+        // number of timesteps before a transition takes effect depends on the frame rate.
+        app.world_mut()
+            .resource_mut::<FixedMainScheduleOrder>()
+            .insert_after(FixedFirst, StateTransition);
+
         app.init_state::<GameState>()
+            .add_sub_state::<PauseState>()
             .insert_resource(ScoreInfo::default())
+            .init_resource::<SimulationTick>()
+            .register_type::<PlayArea>()
+            .init_resource::<PlayArea>()
             .add_message::<ScoredEvent>()
             .add_message::<JumpedEvent>()
+            .add_message::<PlayerDied>()
+            .configure_sets(
+                FixedUpdate,
+                (
+                    GameplaySystems::Tick,
+                    GameplaySystems::Input,
+                    GameplaySystems::Movement,
+                    GameplaySystems::Rules,
+                )
+                    .chain(),
+            )
             .add_systems(OnEnter(GameState::Ready), spawn_level)
             .add_systems(OnEnter(GameState::Playing), start_game)
+            .add_systems(OnEnter(PauseState::Paused), pause_time)
+            .add_systems(OnExit(PauseState::Paused), resume_time)
             .add_systems(
-                Update,
+                FixedUpdate,
                 (
-                    end_game
-                        .run_if(in_state(GameState::Dead).and(on_timer(Duration::from_secs(1))))
-                        .after(check_for_collisions),
-                    (check_for_game_start, force_no_rotation).run_if(in_state(GameState::Ready)),
-                    jump.run_if(in_state(GameState::Playing)),
-                    (ramp_up_speed, check_for_collisions, check_for_out_of_bounds)
-                        .chain()
+                    advance_tick
+                        .in_set(GameplaySystems::Tick)
                         .run_if(in_state(GameState::Playing)),
-                    scoring,
+                    (
+                        (check_for_game_start, cycle_difficulty, force_no_rotation)
+                            .run_if(in_state(GameState::Ready)),
+                        jump.run_if(in_state(GameState::Playing)),
+                    )
+                        .in_set(GameplaySystems::Input),
+                    (
+                        track_gap_passes,
+                        scoring,
+                        (check_for_hits, check_for_out_of_bounds, die)
+                            .chain()
+                            .run_if(in_state(GameState::Playing)),
+                        end_game.run_if(
+                            in_state(GameState::Dead)
+                                .or(in_state(GameState::Finished))
+                                .and(on_timer(Duration::from_secs(1))),
+                        ),
+                    )
+                        .chain()
+                        .in_set(GameplaySystems::Rules),
                 ),
             );
     }
@@ -64,12 +356,21 @@ impl Plugin for StateTransitionPlugin {
 fn start_game(
     mut commands: Commands,
     mut player_query: Single<(Entity, &mut LinearVelocity), With<LockedAxes>>,
-    mut scene_settings: ResMut<SceneSettings>,
     player_settings: Res<PlayerSettings>,
     mut score_info: ResMut<ScoreInfo>,
+    mut tick: ResMut<SimulationTick>,
+    seed_mode: Res<SeedMode>,
 ) {
-    scene_settings.pipe_speed = 5.0;
     score_info.current_score = 0;
+    score_info.bonus_score = 0;
+    score_info.combo = 0;
+    *tick = SimulationTick::default();
+
+    // Yesterday's daily high score doesn't count for today's challenge
+    if *seed_mode == SeedMode::Daily && score_info.daily_day != current_day() {
+        score_info.daily_day = current_day();
+        score_info.daily_high_scores.clear();
+    }
 
     let player = player_query.0;
     let velocity = &mut player_query.1;
@@ -86,46 +387,163 @@ fn start_game(
     velocity.y = player_settings.jump_velocity;
 }
 
-fn ramp_up_speed(mut scene_settings: ResMut<SceneSettings>, time: Res<Time>) {
-    let max_pipe_speed = 8.0;
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
 
-    if scene_settings.pipe_speed < max_pipe_speed {
-        scene_settings.pipe_speed += 0.2 * time.delta_secs();
-    }
+fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
 
-    #[cfg(feature = "max_difficulty")]
-    {
-        scene_settings.pipe_speed = max_pipe_speed;
-    }
+fn advance_tick(mut tick: ResMut<SimulationTick>) {
+    tick.0 += 1;
 }
 
-fn check_for_collisions(
-    collisions: Collisions,
-    mut scene_settings: ResMut<SceneSettings>,
-    mut next_state: ResMut<NextState<GameState>>,
-    player: Single<Entity, With<LockedAxes>>,
+// A hit breaks the shield first, then takes health if the player has any
+fn check_for_hits(
     mut commands: Commands,
+    collisions: Collisions,
+    layers_query: Query<&CollisionLayers>,
+    collider_query: Query<&ColliderOf>,
+    pipe_query: Query<Has<UpperPipe>, With<Pipe>>,
+    player: Single<(Entity, &Position, Option<&mut Health>, Has<Invulnerable>), With<Player>>,
+    mut effects: ResMut<ActiveEffects>,
+    mut player_died: MessageWriter<PlayerDied>,
+    score_info: Res<ScoreInfo>,
+    tick: Res<SimulationTick>,
+    time: Res<Time<Fixed>>,
 ) {
-    if collisions.iter().next().is_some() {
-        scene_settings.pipe_speed = 0.0;
+    let (player, player_position, health, invulnerable) = player.into_inner();
+
+    let hit = collisions.collisions_with(player).find_map(|contact_pair| {
+        let other = if contact_pair.collider1 == player {
+            contact_pair.collider2
+        } else {
+            contact_pair.collider1
+        };
+
+        let is_obstacle = layers_query
+            .get(other)
+            .is_ok_and(|layers| layers.memberships.has_all(GameLayer::Obstacle));
+        let position = contact_pair
+            .find_deepest_contact()
+            .map_or(player_position.0, |contact| contact.point);
 
-        commands.entity(*player).insert((LockedAxes::new(),));
+        is_obstacle.then_some((other, position))
+    });
 
-        next_state.set(GameState::Dead);
+    let Some((obstacle, position)) = hit else {
+        return;
+    };
+    if invulnerable {
+        return;
     }
+
+    if effects.absorb_crash() {
+        commands
+            .entity(player)
+            .insert(Invulnerable::for_seconds(SHIELD_GRACE_SECONDS));
+        return;
+    }
+
+    if let Some(mut health) = health {
+        health.current = health.current.saturating_sub(1);
+        if health.current > 0 {
+            commands
+                .entity(player)
+                .insert(Invulnerable::for_seconds(health.invulnerability));
+            return;
+        }
+    }
+
+    let cause = match collider_query
+        .get(obstacle)
+        .and_then(|collider| pipe_query.get(collider.body))
+    {
+        Ok(true) => DeathCause::UpperPipe,
+        Ok(false) => DeathCause::LowerPipe,
+        Err(_) => DeathCause::Obstacle,
+    };
+
+    player_died.write(PlayerDied {
+        cause,
+        position,
+        score: score_info.current_score,
+        total_score: score_info.total_score(),
+        seconds: **tick as f32 * time.timestep().as_secs_f32(),
+    });
 }
 
 fn check_for_out_of_bounds(
-    mut next_state: ResMut<NextState<GameState>>,
-    player: Single<&GlobalTransform, With<LockedAxes>>,
-    mut scene_settings: ResMut<SceneSettings>,
+    player: Single<(&mut Position, &mut LinearVelocity), With<Player>>,
+    play_area: ActivePlayArea,
+    mut player_died: MessageWriter<PlayerDied>,
+    score_info: Res<ScoreInfo>,
+    tick: Res<SimulationTick>,
+    time: Res<Time<Fixed>>,
 ) {
-    if player.translation().y < -20.0 {
-        scene_settings.pipe_speed = 0.0;
-        next_state.set(GameState::Dead);
+    let (mut position, mut velocity) = player.into_inner();
+    let play_area = play_area.get();
+
+    let (policy, edge, opposite_edge, cause) = if position.y < play_area.bottom {
+        (
+            play_area.bottom_policy,
+            play_area.bottom,
+            play_area.top,
+            DeathCause::Fell,
+        )
+    } else if position.y > play_area.top {
+        (
+            play_area.top_policy,
+            play_area.top,
+            play_area.bottom,
+            DeathCause::Ceiling,
+        )
+    } else {
+        return;
+    };
+
+    match policy {
+        BoundsPolicy::Kill => {
+            player_died.write(PlayerDied {
+                cause,
+                position: position.0,
+                score: score_info.current_score,
+                total_score: score_info.total_score(),
+                seconds: **tick as f32 * time.timestep().as_secs_f32(),
+            });
+        }
+        BoundsPolicy::Clamp => {
+            position.y = edge;
+            velocity.y = 0.0;
+        }
+        BoundsPolicy::Wrap => position.y += opposite_edge - edge,
     }
 }
 
+fn die(
+    mut commands: Commands,
+    mut player_died: MessageReader<PlayerDied>,
+    mut scene_settings: ResMut<SceneSettings>,
+    mut next_state: ResMut<NextState<GameState>>,
+    player: Single<Entity, With<Player>>,
+) {
+    let Some(died) = player_died.read().last() else {
+        return;
+    };
+    debug!(
+        "Died: {} at {} after {:.2}s",
+        died.cause, died.score, died.seconds
+    );
+
+    scene_settings.pipe_speed = 0.0;
+
+    // Let the bird tumble down
+    commands.entity(*player).insert(LockedAxes::new());
+
+    next_state.set(GameState::Dead);
+}
+
 // Quick hack to make sure the initial rotation is correct when starting the game
 // Without it the physics engine will sometimes apply a rotation the first frame
 // after respawning.
@@ -136,63 +554,185 @@ fn force_no_rotation(
     ***player_rotation = Quaternion::from_rotation_z(player_settings.initial_rotation);
 }
 
-fn end_game(
-    mut commands: Commands,
-    player: Single<Entity, With<LockedAxes>>,
-    mut next_state: ResMut<NextState<GameState>>,
-    pipe_query: Query<Entity, With<PipePair>>,
-    player_settings: Res<PlayerSettings>,
+fn end_game(mut commands: Commands) {
+    commands.queue(ResetRun);
+}
+
+/// Puts the player back at the start, removes the pipes and goes back to [`GameState::Ready`],
+/// where a new level is spawned.
+pub struct ResetRun;
+
+impl Command for ResetRun {
+    fn apply(self, world: &mut World) {
+        let transform = initial_transform(world.resource::<PlayerSettings>());
+
+        let player = world
+            .query_filtered::<Entity, With<Player>>()
+            .single(world)
+            .unwrap();
+        world.entity_mut(player).insert((
+            LockedAxes::new()
+                .lock_translation_x()
+                .lock_translation_z()
+                .lock_translation_y(),
+            LinearVelocity::ZERO,
+            AngularVelocity::ZERO,
+            transform,
+        ));
+
+        let pipes: Vec<Entity> = world
+            .query_filtered::<Entity, Or<(With<PipePair>, With<FinishLine>)>>()
+            .iter(world)
+            .collect();
+        for pipe in pipes {
+            world.despawn(pipe);
+        }
+        world.resource_mut::<SceneSettings>().pipe_speed = 0.0;
+
+        world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Ready);
+    }
+}
+
+// Remember how close the player flies to the middle and the edges of every gap it is in
+fn track_gap_passes(
+    collisions: Collisions,
+    mut sensor_query: Query<(&mut GapSensor, &Position, &ChildOf)>,
+    pair_query: Query<&PipePair>,
+    player: Single<(Entity, &Position, &Rotation, &Collider), With<Player>>,
 ) {
-    // todo: Factor out into something like a player-reset bundle
-    commands.entity(*player).insert((
-        LockedAxes::new()
-            .lock_translation_x()
-            .lock_translation_z()
-            .lock_translation_y(),
-        LinearVelocity::ZERO,
-        AngularVelocity::ZERO,
-        Transform::from_translation(player_settings.initial_position),
-    ));
+    let (player, position, rotation, collider) = *player;
+    let bird = collider.aabb(position.0, *rotation);
+
+    for contact_pair in collisions.collisions_with(player) {
+        let other = if contact_pair.collider1 == player {
+            contact_pair.collider2
+        } else {
+            contact_pair.collider1
+        };
+        let Ok((mut sensor, sensor_position, child_of)) = sensor_query.get_mut(other) else {
+            continue;
+        };
+        let Ok(pipe_pair) = pair_query.get(child_of.parent()) else {
+            continue;
+        };
 
-    next_state.set(GameState::Ready);
+        // The sensor sits in the middle of the gap
+        let top = sensor_position.y + pipe_pair.gap_y / 2.0;
+        let bottom = sensor_position.y - pipe_pair.gap_y / 2.0;
+        let offset = (position.y - sensor_position.y).abs();
+        let clearance = (top - bird.max.y).min(bird.min.y - bottom);
 
-    for ent in pipe_query.iter() {
-        commands.entity(ent).despawn();
+        sensor.max_offset = sensor.max_offset.max(offset);
+        sensor.min_clearance = sensor.min_clearance.min(clearance);
     }
 }
 
-fn scoring(
-    mut commands: Commands,
-    pipe_query: Query<(Entity, &Transform), (With<PipePair>, Without<Scored>)>,
-    scored_pipe_query: Query<(Entity, &Transform), With<Scored>>,
+// A point for every gap sensor the player leaves. It can't leave one without passing the gap, other
+// than by crashing into a pipe. Perfect passes and near misses in a row add up to a combo, worth
+// bonus points.
+pub(crate) fn scoring(
+    mut collision_end: MessageReader<CollisionEnd>,
+    player_query: Query<(), With<Player>>,
+    mut sensor_query: Query<&mut GapSensor>,
+    state: Res<State<GameState>>,
     mut score_info: ResMut<ScoreInfo>,
     mut scored_event: MessageWriter<ScoredEvent>,
+    seed_mode: Res<SeedMode>,
+    difficulty: Res<Difficulty>,
+    assist: Res<DifficultyAssist>,
+    attract_demo: Option<Res<AttractDemo>>,
+    course: Option<Res<Course>>,
+    effects: Res<ActiveEffects>,
 ) {
-    let score_boundary = 0.0;
+    // Each preset ranks its own pipes only, not the ones the director eased
+    let counts_for_high_score = attract_demo.is_none() && assist.is_none() && course.is_none();
+
+    for event in collision_end.read() {
+        let sensor = if player_query.contains(event.collider1) {
+            event.collider2
+        } else if player_query.contains(event.collider2) {
+            event.collider1
+        } else {
+            continue;
+        };
+        let Ok(mut gap_sensor) = sensor_query.get_mut(sensor) else {
+            continue;
+        };
 
-    for (pipe_entity, pipe_transform) in &pipe_query {
-        if pipe_transform.translation.x < score_boundary {
-            commands.entity(pipe_entity).insert(Scored);
+        // Ready for the next time the pipe comes around
+        let pass = PassKind::of(&gap_sensor);
+        *gap_sensor = GapSensor::default();
 
-            score_info.current_score += 1;
+        // The bird can still fall out of a gap after dying, and the pipes are despawned with the
+        // bird in between them when the level is reset
+        if *state != GameState::Playing {
+            continue;
+        }
 
-            if score_info.current_score > score_info.high_score {
-                score_info.high_score = score_info.current_score;
-            }
+        score_info.current_score += 1;
 
-            scored_event.write(ScoredEvent);
+        score_info.combo = match pass {
+            PassKind::Plain => 0,
+            PassKind::Perfect | PassKind::NearMiss => score_info.combo + 1,
+        };
+        // Double score only adds bonus points, so the score keeps counting pipes
+        let bonus = score_info.combo.min(MAX_COMBO_BONUS) + effects.score_multiplier() - 1;
+        score_info.bonus_score += bonus;
 
-            #[cfg(feature = "debugging")]
-            println!(
-                "PB: {}, Current Score: {}",
-                score_info.high_score, score_info.current_score
-            );
+        let current_score = score_info.current_score;
+        let high_score = score_info.high_score_for_mut(*seed_mode, *difficulty);
+        if current_score > *high_score && counts_for_high_score {
+            *high_score = current_score;
         }
+
+        scored_event.write(ScoredEvent {
+            pass,
+            bonus,
+            combo: score_info.combo,
+        });
+
+        #[cfg(feature = "debugging")]
+        println!(
+            "PB: {}, Current Score: {}, {pass:?} combo {}",
+            score_info.high_score_for(*seed_mode, *difficulty),
+            score_info.current_score,
+            score_info.combo
+        );
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for (pipe_entity, pipe_transform) in &scored_pipe_query {
-        if pipe_transform.translation.x > score_boundary + 1.0 {
-            commands.entity(pipe_entity).remove::<Scored>();
+    fn sensor(max_offset: f32, min_clearance: f32) -> GapSensor {
+        GapSensor {
+            max_offset,
+            min_clearance,
         }
     }
+
+    #[test]
+    fn pass_kind() {
+        assert_eq!(PassKind::of(&sensor(0.3, 1.0)), PassKind::Perfect);
+        assert_eq!(PassKind::of(&sensor(1.0, 0.1)), PassKind::NearMiss);
+        assert_eq!(PassKind::of(&sensor(1.0, 0.5)), PassKind::Plain);
+    }
+
+    #[test]
+    fn near_miss_wins_over_perfect() {
+        assert_eq!(PassKind::of(&sensor(0.3, 0.1)), PassKind::NearMiss);
+    }
+
+    #[test]
+    fn touching_a_pipe_is_no_near_miss() {
+        assert_eq!(PassKind::of(&sensor(1.0, -0.1)), PassKind::Plain);
+    }
+
+    #[test]
+    fn sensor_the_bird_never_entered() {
+        assert_eq!(PassKind::of(&GapSensor::default()), PassKind::Plain);
+    }
 }
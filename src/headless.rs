@@ -0,0 +1,24 @@
+use bevy::app::PluginGroupBuilder;
+use bevy::input::InputPlugin;
+use bevy::mesh::MeshPlugin;
+use bevy::prelude::*;
+use bevy::scene::ScenePlugin;
+use bevy::state::app::StatesPlugin;
+
+/// The Bevy plugins needed to run [`BevyBirdPlugin::headless`](crate::BevyBirdPlugin::headless)
+/// without a window or GPU.
+pub struct HeadlessPlugins;
+
+impl PluginGroup for HeadlessPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add_group(MinimalPlugins)
+            .add(TransformPlugin)
+            .add(InputPlugin)
+            .add(StatesPlugin)
+            // No assets are loaded, but the physics plugins expect the mesh and scene types to exist
+            .add(AssetPlugin::default())
+            .add(MeshPlugin)
+            .add(ScenePlugin)
+    }
+}
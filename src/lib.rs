@@ -0,0 +1,281 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+mod audio;
+mod editor;
+pub mod env;
+pub mod gameplay;
+mod headless;
+pub mod player;
+pub mod replay;
+pub mod scene;
+mod score_save;
+pub mod stdio;
+mod ui;
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use std::time::Duration;
+
+pub use editor::CourseFile;
+pub use gameplay::{
+    BoundsPolicy, DeathCause, GameLayer, GameState, GameplaySystems, PauseState, PlayArea,
+    PlayerDied, ScoreInfo, SimulationTick,
+};
+pub use headless::HeadlessPlugins;
+pub use player::PlayerSettings;
+pub use player::autopilot::Autopilot;
+pub use replay::Replay;
+pub use scene::course::{Course, CourseCompleted};
+pub use scene::difficulty::{Difficulty, DifficultyAssist, DifficultyCurve};
+pub use scene::{SceneSettings, SeedMode};
+pub use stdio::StdioPlugin;
+
+/// Adds the whole game to an app.
+///
+/// The app is expected to already contain the Bevy plugins it needs, e.g. `DefaultPlugins`.
+/// Physics is added by this plugin, so don't add `PhysicsPlugins` yourself.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_bird::BevyBirdPlugin;
+/// App::new()
+///     .add_plugins(DefaultPlugins)
+///     .add_plugins(BevyBirdPlugin::default().with_audio(false))
+///     .run();
+/// ```
+pub struct BevyBirdPlugin {
+    rendering: bool,
+    audio: bool,
+    ui: bool,
+    saving: bool,
+    player_settings: PlayerSettings,
+    passable_only: bool,
+    play_area: PlayArea,
+    playback: Option<Replay>,
+    seed_mode: SeedMode,
+    autopilot: bool,
+    attract_mode: Option<Duration>,
+    director: bool,
+    difficulty: Difficulty,
+    difficulty_curve: Option<DifficultyCurve>,
+    course: Option<Course>,
+    editor: bool,
+    course_file: CourseFile,
+}
+
+impl Default for BevyBirdPlugin {
+    fn default() -> Self {
+        Self {
+            rendering: true,
+            audio: true,
+            ui: true,
+            saving: cfg!(feature = "desktop"),
+            player_settings: PlayerSettings::default(),
+            passable_only: false,
+            play_area: PlayArea::default(),
+            playback: None,
+            seed_mode: SeedMode::default(),
+            autopilot: false,
+            attract_mode: Some(Duration::from_secs(10)),
+            director: true,
+            difficulty: Difficulty::default(),
+            difficulty_curve: None,
+            course: None,
+            editor: true,
+            course_file: CourseFile::default(),
+        }
+    }
+}
+
+impl BevyBirdPlugin {
+    /// Only the gameplay: physics, game states, scoring and pipe recycling.
+    ///
+    /// Use together with [`HeadlessPlugins`] to run the game without a window or GPU.
+    pub fn headless() -> Self {
+        Self {
+            rendering: false,
+            audio: false,
+            ui: false,
+            saving: false,
+            attract_mode: None,
+            director: false,
+            editor: false,
+            ..default()
+        }
+    }
+
+    /// Spawn the camera, lights and models. Turn this off when the host app brings its own camera.
+    pub fn with_rendering(mut self, rendering: bool) -> Self {
+        self.rendering = rendering;
+        self
+    }
+
+    pub fn with_audio(mut self, audio: bool) -> Self {
+        self.audio = audio;
+        self
+    }
+
+    pub fn with_ui(mut self, ui: bool) -> Self {
+        self.ui = ui;
+        self
+    }
+
+    /// Load and store the high score in `./score.ron` and the last run in `./last_replay.ron`.
+    /// Enabled by default with the `desktop` feature.
+    pub fn with_saving(mut self, saving: bool) -> Self {
+        self.saving = saving;
+        self
+    }
+
+    pub fn with_player_settings(mut self, player_settings: PlayerSettings) -> Self {
+        self.player_settings = player_settings;
+        self
+    }
+
+    /// Only generate pipe heights the [`scene::solver`] can prove to be passable, see
+    /// [`SceneSettings::passable_only`]. The other pipe settings follow the [`DifficultyCurve`].
+    pub fn with_passable_only(mut self, passable_only: bool) -> Self {
+        self.passable_only = passable_only;
+        self
+    }
+
+    /// How high and low the bird can fly, and what happens when it leaves the play area.
+    /// By default it dies when it flies above the pipes or falls far below them.
+    pub fn with_play_area(mut self, play_area: PlayArea) -> Self {
+        self.play_area = play_area;
+        self
+    }
+
+    /// The difficulty preset of the first run, the player can switch before every run
+    pub fn with_difficulty(mut self, difficulty: Difficulty) -> Self {
+        self.difficulty = difficulty;
+        self
+    }
+
+    /// Follow this curve on every difficulty instead of the ones in `assets/difficulty/`.
+    /// Headless apps load no assets and use [`DifficultyCurve::preset`] unless one is given here.
+    ///
+    /// # Panics
+    ///
+    /// If the curve has no keyframes, see [`DifficultyCurve::validate`]
+    pub fn with_difficulty_curve(mut self, difficulty_curve: DifficultyCurve) -> Self {
+        let difficulty_curve = difficulty_curve
+            .validate()
+            .unwrap_or_else(|err| panic!("{err}"));
+        self.difficulty_curve = Some(difficulty_curve);
+        self
+    }
+
+    /// Play this course every run instead of the endless random pipes, see [`scene::course`]
+    pub fn with_course(mut self, course: Course) -> Self {
+        self.course = Some(course);
+        self
+    }
+
+    /// Let the player build courses in the editor by pressing `E` before a run.
+    /// On by default, except when headless. Needs rendering.
+    pub fn with_editor(mut self, editor: bool) -> Self {
+        self.editor = editor;
+        self
+    }
+
+    /// Where the editor saves and loads the course, `./course.course.ron` by default
+    pub fn with_course_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.course_file = CourseFile(path.into());
+        self
+    }
+
+    /// How the pipe layout of each run is seeded
+    pub fn with_seed_mode(mut self, seed_mode: SeedMode) -> Self {
+        self.seed_mode = seed_mode;
+        self
+    }
+
+    /// Let the [`Autopilot`] fly the bird from the start, e.g. for soak tests. It starts a new run
+    /// whenever the game is ready. The [`Autopilot`] resource can also be toggled later on.
+    pub fn with_autopilot(mut self, autopilot: bool) -> Self {
+        self.autopilot = autopilot;
+        self
+    }
+
+    /// Let the autopilot play a demo after sitting idle in [`GameState::Ready`] for this long,
+    /// until the player presses jump. On after 10 seconds by default, except when headless.
+    pub fn with_attract_mode(mut self, idle_time: Option<Duration>) -> Self {
+        self.attract_mode = idle_time;
+        self
+    }
+
+    /// Make the pipes easier for players who keep dying early, see [`scene::director`].
+    /// On by default, except when headless. Assisted runs don't count for the high score.
+    pub fn with_director(mut self, director: bool) -> Self {
+        self.director = director;
+        self
+    }
+
+    /// Play back a recorded run instead of reading the player's input for the first run
+    pub fn with_replay_playback(mut self, replay: Replay) -> Self {
+        self.playback = Some(replay);
+        self
+    }
+}
+
+impl Plugin for BevyBirdPlugin {
+    fn build(&self, app: &mut App) {
+        // Inserted before the sub-plugins so they don't fall back to their defaults
+        app.insert_resource(self.player_settings.clone())
+            .insert_resource(SceneSettings {
+                passable_only: self.passable_only,
+                ..default()
+            })
+            .insert_resource(self.play_area.clone())
+            .insert_resource(self.seed_mode)
+            .insert_resource(self.difficulty)
+            .add_plugins(PhysicsPlugins::default())
+            .add_plugins(player::PlayerPlugin {
+                rendering: self.rendering,
+            })
+            .add_plugins(scene::ScenePlugin {
+                rendering: self.rendering,
+                difficulty_curve: self.difficulty_curve.clone(),
+            })
+            .add_plugins(gameplay::StateTransitionPlugin)
+            .add_plugins(replay::ReplayPlugin {
+                playback: self.playback.clone(),
+                saving: self.saving,
+            });
+
+        if let Some(course) = &self.course {
+            app.insert_resource(course.clone());
+        }
+
+        app.add_plugins(player::autopilot::AutopilotPlugin {
+            active: self.autopilot,
+        });
+
+        if let Some(idle_time) = self.attract_mode {
+            app.add_plugins(player::autopilot::AttractModePlugin { idle_time });
+        }
+
+        if self.director {
+            app.add_plugins(scene::director::DirectorPlugin);
+        }
+
+        if self.editor && self.rendering {
+            app.add_plugins(editor::EditorPlugin {
+                course_file: self.course_file.clone(),
+            });
+        }
+
+        if self.ui {
+            app.add_plugins(ui::UiPlugin);
+        }
+
+        if self.audio {
+            app.add_plugins(audio::GameAudioPlugin);
+        }
+
+        if self.saving {
+            app.add_plugins(score_save::SavePlugin);
+        }
+    }
+}
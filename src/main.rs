@@ -1,18 +1,84 @@
-#![allow(clippy::too_many_arguments, clippy::type_complexity)]
-
-mod audio;
-mod gameplay;
-mod player;
-mod scene;
-mod score_save;
-mod ui;
-
-use avian3d::prelude::*;
 use bevy::asset::AssetMetaCheck;
 use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_bird::{
+    BevyBirdPlugin, Course, Difficulty, HeadlessPlugins, Replay, SeedMode, StdioPlugin,
+};
 
 fn main() {
     let mut app = App::new();
+    let mut bevy_bird = BevyBirdPlugin::default();
+    let mut stdio = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--replay" => {
+                let path = args.next().expect("--replay needs a path to a replay file");
+                let replay = Replay::load(&path).unwrap_or_else(|err| {
+                    eprintln!("Could not load replay {path}: {err}");
+                    std::process::exit(1);
+                });
+                bevy_bird = bevy_bird.with_replay_playback(replay);
+            }
+            "--course" => {
+                let path = args.next().expect("--course needs a path to a course file");
+                let course = Course::load(&path).unwrap_or_else(|err| {
+                    eprintln!("Could not load course {path}: {err}");
+                    std::process::exit(1);
+                });
+                bevy_bird = bevy_bird.with_course(course).with_course_file(path);
+            }
+            "--edit" => {
+                let path = args.next().expect("--edit needs a path to a course file");
+                bevy_bird = bevy_bird.with_course_file(path);
+            }
+            "--seed" => {
+                let seed = args
+                    .next()
+                    .and_then(|seed| seed.parse().ok())
+                    .expect("--seed needs a number");
+                bevy_bird = bevy_bird.with_seed_mode(SeedMode::Fixed(seed));
+            }
+            "--difficulty" => {
+                let difficulty = args
+                    .next()
+                    .and_then(|name| {
+                        Difficulty::ALL
+                            .into_iter()
+                            .find(|difficulty| difficulty.to_string().eq_ignore_ascii_case(&name))
+                    })
+                    .expect("--difficulty needs one of easy, normal, hard or max");
+                bevy_bird = bevy_bird.with_difficulty(difficulty);
+            }
+            "--daily" => bevy_bird = bevy_bird.with_seed_mode(SeedMode::Daily),
+            "--stdio" => stdio = true,
+            _ => eprintln!("Ignoring unknown argument {arg}"),
+        }
+    }
+
+    // Played by another program over stdin and stdout, see the `stdio` module
+    if stdio {
+        // Everything `BevyBirdPlugin::headless` turns off, keeping the arguments above. The attract
+        // mode's autopilot would take over from the bot after a while.
+        app.add_plugins(HeadlessPlugins)
+            .add_plugins(
+                bevy_bird
+                    .with_rendering(false)
+                    .with_audio(false)
+                    .with_ui(false)
+                    .with_saving(false)
+                    .with_attract_mode(None)
+                    .with_director(false)
+                    .with_editor(false),
+            )
+            .add_plugins(StdioPlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(
+                Time::<Fixed>::default().timestep(),
+            ))
+            .run();
+        return;
+    }
 
     app.add_plugins(
         DefaultPlugins
@@ -28,21 +94,13 @@ fn main() {
                 ..default()
             }),
     )
-    .add_plugins(PhysicsPlugins::default())
-    .add_plugins(player::PlayerPlugin)
-    .add_plugins(scene::ScenePlugin)
-    .add_plugins(gameplay::StateTransitionPlugin)
-    .add_plugins(ui::UiPlugin)
-    .add_plugins(audio::GameAudioPlugin);
+    .add_plugins(bevy_bird);
 
     // todo mipmaps
-    #[cfg(feature = "desktop")]
-    {
-        app.add_plugins(score_save::SavePlugin);
-    }
 
     #[cfg(feature = "debugging")]
     {
+        use avian3d::prelude::*;
         use bevy_inspector_egui::bevy_egui::EguiPlugin;
         use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
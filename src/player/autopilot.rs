@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use crate::gameplay::{GameState, GameplaySystems, ResetRun};
+use crate::player::Player;
+use crate::player::inputs::{Action, create_input_map};
+use crate::replay::ReplayPlayback;
+use crate::scene::pipes::PipePair;
+
+/// Whether the autopilot is flying the bird. Flip it at any time to hand the bird over.
+#[derive(Resource, Default, Debug)]
+pub struct Autopilot {
+    pub active: bool,
+}
+
+/// A simple rule-based bot that aims for the middle of the next gap. It presses the same jump
+/// action a player would, so it also starts a new run whenever the game is ready.
+///
+/// Always added by [`crate::BevyBirdPlugin`], see [`crate::BevyBirdPlugin::with_autopilot`].
+pub(crate) struct AutopilotPlugin {
+    /// Fly from the start, e.g. for soak tests. Otherwise toggle [`Autopilot`] when needed.
+    pub active: bool,
+}
+
+impl Plugin for AutopilotPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Autopilot {
+            active: self.active,
+        })
+        .add_systems(
+            FixedUpdate,
+            fly.after(GameplaySystems::Tick)
+                .before(GameplaySystems::Input)
+                .run_if(|autopilot: Res<Autopilot>| autopilot.active),
+        );
+    }
+}
+
+fn fly(
+    player: Single<(&Transform, &LinearVelocity, &mut ActionState<Action>), With<Player>>,
+    pipe_query: Query<(&Transform, &PipePair)>,
+    state: Res<State<GameState>>,
+) {
+    let (transform, velocity, mut action_state) = player.into_inner();
+
+    let target_y = pipe_query
+        .iter()
+        .filter(|(transform, _)| transform.translation.x > -1.5)
+        .min_by(|(a, _), (b, _)| a.translation.x.total_cmp(&b.translation.x))
+        .map_or(0.0, |(transform, pipe)| {
+            transform.translation.y + pipe.gap_y / 2.0
+        });
+
+    // Release after every press, a jump only registers when the action is newly pressed
+    if action_state.pressed(&Action::Jump) {
+        action_state.release(&Action::Jump);
+    } else if *state == GameState::Ready
+        || (transform.translation.y < target_y - 0.5 && velocity.y <= 0.0)
+    {
+        action_state.press(&Action::Jump);
+    }
+}
+
+/// Lets the autopilot play a demo when nobody has started a run for a while
+pub(crate) struct AttractModePlugin {
+    pub idle_time: Duration,
+}
+
+/// An attract mode demo is playing. Its scores don't count.
+#[derive(Resource)]
+pub struct AttractDemo;
+
+#[derive(Resource)]
+struct IdleTimer(Timer);
+
+// Listens for the player's input while the autopilot has the bird
+#[derive(Component)]
+struct DemoListener;
+
+impl Plugin for AttractModePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(IdleTimer(Timer::new(self.idle_time, TimerMode::Once)))
+            .add_systems(OnEnter(GameState::Ready), reset_idle_timer)
+            .add_systems(
+                FixedUpdate,
+                (
+                    start_demo.run_if(
+                        in_state(GameState::Ready)
+                            .and(not(resource_exists::<AttractDemo>))
+                            .and(not(resource_exists::<ReplayPlayback>)),
+                    ),
+                    stop_demo.before(fly).run_if(resource_exists::<AttractDemo>),
+                )
+                    .before(GameplaySystems::Input),
+            );
+    }
+}
+
+fn reset_idle_timer(mut idle_timer: ResMut<IdleTimer>) {
+    idle_timer.0.reset();
+}
+
+fn start_demo(
+    mut commands: Commands,
+    mut idle_timer: ResMut<IdleTimer>,
+    mut autopilot: ResMut<Autopilot>,
+    player: Single<Entity, With<Player>>,
+    time: Res<Time>,
+) {
+    if !idle_timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    info!("Nobody is playing, starting the attract mode demo");
+
+    autopilot.active = true;
+    commands.insert_resource(AttractDemo);
+
+    // Move the player's input elsewhere so it doesn't fight the autopilot
+    commands.entity(*player).remove::<InputMap<Action>>();
+    commands.spawn((Name::new("DemoListener"), DemoListener, create_input_map()));
+}
+
+fn stop_demo(
+    mut commands: Commands,
+    listener: Single<(Entity, &ActionState<Action>), With<DemoListener>>,
+    mut autopilot: ResMut<Autopilot>,
+    player: Single<Entity, With<Player>>,
+) {
+    let (listener, action_state) = *listener;
+
+    if !action_state.just_pressed(&Action::Jump) {
+        return;
+    }
+
+    autopilot.active = false;
+    commands.remove_resource::<AttractDemo>();
+    commands.entity(listener).despawn();
+    commands.entity(*player).insert(create_input_map());
+    commands.queue(ResetRun);
+}
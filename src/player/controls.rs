@@ -2,12 +2,13 @@ use avian3d::prelude::*;
 use bevy::prelude::*;
 use leafwing_input_manager::prelude::*;
 
-use crate::gameplay::{GameState, JumpedEvent};
-use crate::player::PlayerSettings;
+use crate::gameplay::{GameState, JumpedEvent, ResetRun};
 use crate::player::inputs::Action;
+use crate::player::{Player, PlayerSettings};
+use crate::scene::difficulty::Difficulty;
 
 pub fn jump(
-    query: Single<(&ActionState<Action>, &mut LinearVelocity)>,
+    query: Single<(&ActionState<Action>, &mut LinearVelocity), With<Player>>,
     player_settings: Res<PlayerSettings>,
     mut jumped_event: MessageWriter<JumpedEvent>,
 ) {
@@ -20,10 +21,24 @@ pub fn jump(
 }
 
 pub fn check_for_game_start(
-    action_state: Single<&ActionState<Action>>,
+    action_state: Single<&ActionState<Action>, With<Player>>,
     mut next_state: ResMut<NextState<GameState>>,
 ) {
     if action_state.just_pressed(&Action::Jump) {
         next_state.set(GameState::Playing);
     }
 }
+
+pub fn cycle_difficulty(
+    mut commands: Commands,
+    action_state: Single<&ActionState<Action>, With<Player>>,
+    mut difficulty: ResMut<Difficulty>,
+) {
+    if action_state.just_pressed(&Action::CycleDifficulty) {
+        *difficulty = difficulty.next();
+        info!("Difficulty: {}", *difficulty);
+
+        // Respawn the level with the pipes of the new difficulty
+        commands.queue(ResetRun);
+    }
+}
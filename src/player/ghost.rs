@@ -0,0 +1,155 @@
+use avian3d::prelude::*;
+use bevy::light::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::scene::SceneInstanceReady;
+
+use crate::gameplay::{GameState, GameplaySystems, SimulationTick};
+use crate::player::{Player, PlayerSettings};
+use crate::replay::{PersonalBests, Replay};
+use crate::scene::difficulty::Difficulty;
+
+// Slightly behind the player so the two birds don't clip into each other
+const DEPTH_OFFSET: Vec3 = Vec3::new(0.0, 0.0, -1.0);
+
+/// A bird without a collider that flies the personal best run of the chosen difficulty alongside
+/// the player.
+///
+/// It only replays the recorded jumps, so it follows its own run even when the pipes are different.
+#[derive(Component, Default)]
+pub struct Ghost {
+    run: Option<Replay>,
+    velocity: f32,
+    /// The score of the run so far including bonus points, like the player's
+    pub score: u32,
+}
+
+impl Ghost {
+    /// The run being raced, if any
+    pub fn run(&self) -> Option<&Replay> {
+        self.run.as_ref()
+    }
+
+    /// Whether there is a run to race and it's still going
+    pub fn is_racing(&self, tick: u64) -> bool {
+        self.run.as_ref().is_some_and(|run| tick <= run.death_tick)
+    }
+}
+
+pub(crate) struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Playing), start_ghost)
+            .add_systems(OnEnter(GameState::Ready), hide_ghost)
+            .add_systems(
+                FixedUpdate,
+                fly_ghost
+                    .after(GameplaySystems::Input)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+pub(crate) fn ghost_bundle(player_settings: &PlayerSettings) -> impl Bundle {
+    (
+        Name::new("Ghost"),
+        Ghost::default(),
+        Transform::from_translation(player_settings.initial_position + DEPTH_OFFSET)
+            .with_rotation(Quat::from_rotation_z(player_settings.initial_rotation)),
+        Visibility::Hidden,
+    )
+}
+
+fn start_ghost(
+    ghost: Single<(&mut Ghost, &mut Transform, &mut Visibility)>,
+    personal_bests: Res<PersonalBests>,
+    difficulty: Res<Difficulty>,
+    player_settings: Res<PlayerSettings>,
+) {
+    let (mut ghost, mut transform, mut visibility) = ghost.into_inner();
+
+    // Same as the player, the press that starts the game is also a jump
+    ghost.run = personal_bests.get(&difficulty).cloned();
+    ghost.velocity = player_settings.jump_velocity;
+    ghost.score = 0;
+
+    transform.translation = player_settings.initial_position + DEPTH_OFFSET;
+
+    *visibility = if ghost.run.is_some() {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+}
+
+// Integrates the same way as the physics engine, so the ghost ends up where the bird was
+fn fly_ghost(
+    ghost: Single<(&mut Ghost, &mut Transform, &mut Visibility)>,
+    player_gravity: Single<&GravityScale, With<Player>>,
+    gravity: Res<Gravity>,
+    substeps: Res<SubstepCount>,
+    player_settings: Res<PlayerSettings>,
+    tick: Res<SimulationTick>,
+    time: Res<Time>,
+) {
+    let (mut ghost, mut transform, mut visibility) = ghost.into_inner();
+
+    if !ghost.is_racing(**tick) {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+
+    let Some(run) = &ghost.run else {
+        return;
+    };
+
+    let jumped = run.jumps.binary_search(&tick).is_ok();
+    let scored = run
+        .score_ticks
+        .partition_point(|(score_tick, _)| score_tick <= &**tick);
+
+    ghost.score = scored
+        .checked_sub(1)
+        .map_or(0, |index| run.score_ticks[index].1);
+
+    if jumped {
+        ghost.velocity = player_settings.jump_velocity;
+    }
+
+    let substep_secs = time.delta_secs() / substeps.0 as f32;
+    for _ in 0..substeps.0 {
+        ghost.velocity += gravity.0.y * player_gravity.0 * substep_secs;
+        transform.translation.y += ghost.velocity * substep_secs;
+    }
+}
+
+fn hide_ghost(mut ghost: Single<(&mut Ghost, &mut Visibility)>) {
+    ghost.0.run = None;
+    ghost.0.score = 0;
+    *ghost.1 = Visibility::Hidden;
+}
+
+pub(crate) fn make_translucent(
+    scene_ready: On<SceneInstanceReady>,
+    mut commands: Commands,
+    children: Query<&Children>,
+    mut mesh_materials: Query<&mut MeshMaterial3d<StandardMaterial>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for child in children.iter_descendants(scene_ready.entity) {
+        let Ok(mut mesh_material) = mesh_materials.get_mut(child) else {
+            continue;
+        };
+
+        let Some(material) = materials.get(&mesh_material.0) else {
+            continue;
+        };
+
+        let mut ghost_material = material.clone();
+        ghost_material.base_color.set_alpha(0.3);
+        ghost_material.alpha_mode = AlphaMode::Blend;
+
+        mesh_material.0 = materials.add(ghost_material);
+        commands.entity(child).insert(NotShadowCaster);
+    }
+}
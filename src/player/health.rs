@@ -0,0 +1,86 @@
+//! Lets the bird survive hits. Without a [`Health`] component the first hit ends the run.
+//!
+//! Give the bird health with [`PlayerSettings::health`](crate::PlayerSettings::health).
+
+use bevy::prelude::*;
+
+use crate::gameplay::{GameState, GameplaySystems};
+use crate::player::Player;
+
+/// Seconds the bird is [`Invulnerable`] after losing health, unless the settings say otherwise
+pub const DEFAULT_INVULNERABILITY: f32 = 1.5;
+
+#[derive(Component, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct Health {
+    pub current: u32,
+    /// What a run starts with
+    pub max: u32,
+    /// Seconds the bird is [`Invulnerable`] after a hit, so it can get clear of the obstacle
+    pub invulnerability: f32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self {
+            current: max,
+            max,
+            invulnerability: DEFAULT_INVULNERABILITY,
+        }
+    }
+}
+
+/// Hits are ignored while this is on the player
+#[derive(Component, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct Invulnerable {
+    /// Seconds left
+    pub remaining: f32,
+}
+
+impl Invulnerable {
+    pub fn for_seconds(seconds: f32) -> Self {
+        Self { remaining: seconds }
+    }
+}
+
+pub(super) struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Health>()
+            .register_type::<Invulnerable>()
+            .add_systems(OnEnter(GameState::Ready), restore_health)
+            .add_systems(
+                FixedUpdate,
+                wear_off_invulnerability
+                    .in_set(GameplaySystems::Tick)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+fn restore_health(
+    mut commands: Commands,
+    player: Single<(Entity, Option<&mut Health>), With<Player>>,
+) {
+    let (player, health) = player.into_inner();
+
+    if let Some(mut health) = health {
+        health.current = health.max;
+    }
+    commands.entity(player).remove::<Invulnerable>();
+}
+
+fn wear_off_invulnerability(
+    mut commands: Commands,
+    mut player: Single<(Entity, &mut Invulnerable), With<Player>>,
+    time: Res<Time>,
+) {
+    let (entity, invulnerable) = &mut *player;
+
+    invulnerable.remaining -= time.delta_secs();
+    if invulnerable.remaining <= 0.0 {
+        commands.entity(*entity).remove::<Invulnerable>();
+    }
+}
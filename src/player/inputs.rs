@@ -2,8 +2,10 @@ use bevy::prelude::{GamepadButton, KeyCode, MouseButton, Reflect};
 use leafwing_input_manager::prelude::*;
 
 #[derive(Actionlike, PartialEq, Eq, Hash, Clone, Copy, Debug, Reflect)]
-pub(crate) enum Action {
+pub enum Action {
     Jump,
+    /// Switch to the next difficulty preset, only before a run
+    CycleDifficulty,
 }
 
 // Stores "which actions are currently activated"
@@ -14,5 +16,8 @@ pub(crate) fn create_input_map() -> InputMap<Action> {
     input_map.insert(Action::Jump, MouseButton::Left);
     input_map.insert(Action::Jump, GamepadButton::South);
 
+    input_map.insert(Action::CycleDifficulty, KeyCode::KeyD);
+    input_map.insert(Action::CycleDifficulty, GamepadButton::North);
+
     input_map
 }
@@ -1,38 +1,67 @@
+pub mod autopilot;
 pub(crate) mod controls;
-pub(crate) mod inputs;
+pub mod ghost;
+pub mod health;
+pub mod inputs;
 
-use crate::gameplay::JumpedEvent;
+use crate::gameplay::{GameLayer, JumpedEvent};
 use avian3d::prelude::*;
 use bevy::{light::NotShadowCaster, prelude::*};
 use leafwing_input_manager::prelude::*;
 
 use bevy::color::palettes::css::ORANGE;
 
-#[derive(Reflect, Resource, Default)]
+#[derive(Reflect, Resource, Clone)]
 #[reflect(Resource)]
 pub struct PlayerSettings {
     pub initial_position: Vec3,
     pub initial_rotation: f32,
     pub jump_velocity: f32,
+    /// Hits the bird can take in a run, see [`health`]. `None` ends the run on the first hit.
+    pub health: Option<u32>,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        Self {
+            jump_velocity: 10.0,
+            initial_position: Vec3::new(0.0, 1.0, 0.0),
+            initial_rotation: -0.28,
+            health: None,
+        }
+    }
+}
+
+/// The bird falls faster than the default gravity
+pub(crate) const GRAVITY_SCALE: f32 = 4.0;
+
+/// The bird's collider, before the rotation of the [`initial_transform`]
+pub(crate) fn collider() -> Collider {
+    Collider::capsule(0.2, 0.7)
 }
 
 #[derive(Resource, Deref, DerefMut)]
 struct SmokeMaterialHandle(Handle<StandardMaterial>);
 
-pub struct PlayerPlugin;
+pub struct PlayerPlugin {
+    /// Spawn the bird model, smoke and muzzle flash lights
+    pub rendering: bool,
+}
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(InputManagerPlugin::<inputs::Action>::default())
             .register_type::<PlayerSettings>()
-            .insert_resource(PlayerSettings {
-                jump_velocity: 10.0,
-                initial_position: Vec3::new(0.0, 1.0, 0.0),
-                initial_rotation: -0.28,
-            })
-            .insert_resource(SmokeMaterialHandle(Handle::default()))
-            .add_systems(Startup, setup)
-            .add_systems(Update, (gunshot_lighting, smoke_control));
+            .init_resource::<PlayerSettings>()
+            .add_plugins(ghost::GhostPlugin)
+            .add_plugins(health::HealthPlugin)
+            .add_systems(Startup, setup);
+
+        if self.rendering {
+            app.insert_resource(SmokeMaterialHandle(Handle::default()))
+                .add_systems(Startup, setup_visuals.after(setup))
+                .add_systems(Update, (gunshot_lighting, smoke_control));
+        }
     }
 }
 
@@ -40,36 +69,64 @@ impl Plugin for PlayerPlugin {
 #[reflect(Component)]
 pub struct Player;
 
+/// Where the player starts a run. The rotation has to be part of it, otherwise the physics engine
+/// picks up the rotation from the transform and the start of a run depends on how long the game
+/// was waiting for it.
+pub(crate) fn initial_transform(player_settings: &PlayerSettings) -> Transform {
+    Transform::from_translation(player_settings.initial_position)
+        .with_rotation(Quat::from_rotation_z(player_settings.initial_rotation))
+}
+
 #[derive(Component, Default, Reflect)]
 #[reflect(Component)]
 pub struct Smoke;
 
-fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    player_settings: Res<PlayerSettings>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut smoke_material_handle: ResMut<SmokeMaterialHandle>,
-) {
-    let parent = commands
+fn setup(mut commands: Commands, player_settings: Res<PlayerSettings>) {
+    let player = commands
         .spawn((
             Name::new("Player"),
             Player,
             RigidBody::Dynamic,
-            GravityScale(4.0),
+            GravityScale(GRAVITY_SCALE),
             LockedAxes::new()
                 .lock_translation_x()
                 .lock_translation_z()
                 .lock_translation_y(),
             LinearVelocity::ZERO,
-            Collider::capsule(0.2, 0.7),
-            SceneRoot(asset_server.load("objects/bird.glb#Scene0")),
-            Transform::from_translation(player_settings.initial_position),
+            collider(),
+            GameLayer::player(),
+            initial_transform(&player_settings),
             inputs::create_input_map(),
         ))
         .id();
 
+    if let Some(health) = player_settings.health {
+        commands.entity(player).insert(health::Health::new(health));
+    }
+
+    commands.spawn(ghost::ghost_bundle(&player_settings));
+}
+
+fn setup_visuals(
+    mut commands: Commands,
+    player: Single<Entity, With<Player>>,
+    ghost: Single<Entity, With<ghost::Ghost>>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut smoke_material_handle: ResMut<SmokeMaterialHandle>,
+) {
+    let parent = *player;
+
+    commands
+        .entity(parent)
+        .insert(SceneRoot(asset_server.load("objects/bird.glb#Scene0")));
+
+    commands
+        .entity(*ghost)
+        .insert(SceneRoot(asset_server.load("objects/bird.glb#Scene0")))
+        .observe(ghost::make_translucent);
+
     let smoke_material = StandardMaterial {
         alpha_mode: AlphaMode::Blend,
         base_color: Color::srgba(1.0, 1.0, 1.0, 0.0),
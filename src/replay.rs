@@ -0,0 +1,476 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::gameplay::ResetRun;
+use crate::gameplay::{
+    DeathCause, GameState, GameplaySystems, PlayArea, PlayerDied, ScoreInfo, ScoredEvent,
+    SimulationTick,
+};
+use crate::player::Player;
+use crate::player::autopilot::AttractDemo;
+use crate::player::inputs::{Action, create_input_map};
+use crate::scene::course::Course;
+use crate::scene::difficulty::{
+    ActiveDifficultyCurve, Difficulty, DifficultyAssist, DifficultyCurve, DifficultyCurves,
+};
+use crate::scene::{RunSeed, SceneSettings};
+
+/// Bumped whenever a change to the gameplay or the file format makes the replays of a released
+/// version invalid
+pub const REPLAY_VERSION: u32 = 1;
+
+const LAST_REPLAY_PATH: &str = "./last_replay.ron";
+
+fn personal_best_path(difficulty: Difficulty) -> String {
+    format!(
+        "./best_replay_{}.ron",
+        difficulty.to_string().to_lowercase()
+    )
+}
+
+/// The inputs of a single run and the settings it was played with, enough to reproduce it exactly.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Replay {
+    pub version: u32,
+    /// The [`RunSeed`] of the run
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub assist: DifficultyAssist,
+    /// The curve of the difficulty the run followed, without the assist
+    pub curve: DifficultyCurve,
+    pub play_area: PlayArea,
+    /// See [`SceneSettings::passable_only`]
+    pub passable_only: bool,
+    /// The [`SimulationTick`]s on which the jump action was pressed.
+    /// Not including the press that started the run.
+    pub jumps: Vec<u64>,
+    /// The [`SimulationTick`]s on which a pipe was passed, with the score including bonus points
+    /// after it, as shown during the run
+    pub score_ticks: Vec<(u64, u32)>,
+    pub score: u32,
+    pub death_tick: u64,
+    /// What ended the run
+    pub death_cause: Option<DeathCause>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, difficulty: Difficulty) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            difficulty,
+            assist: DifficultyAssist::NONE,
+            curve: DifficultyCurve::preset(difficulty),
+            play_area: PlayArea::default(),
+            passable_only: false,
+            jumps: Vec::new(),
+            score_ticks: Vec::new(),
+            score: 0,
+            death_tick: 0,
+            death_cause: None,
+        }
+    }
+
+    pub fn from_ron(data: &str) -> Result<Self, ReplayError> {
+        let replay: Self = ron::from_str(data).map_err(ReplayError::Parse)?;
+
+        if replay.version != REPLAY_VERSION {
+            return Err(ReplayError::Version(replay.version));
+        }
+
+        Ok(replay)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let data = std::fs::read_to_string(path).map_err(ReplayError::Io)?;
+        Self::from_ron(&data)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let data = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(ReplayError::Serialize)?;
+        std::fs::write(path, data).map_err(ReplayError::Io)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    Version(u32),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "{err}"),
+            ReplayError::Parse(err) => write!(f, "invalid replay: {err}"),
+            ReplayError::Serialize(err) => write!(f, "could not serialize replay: {err}"),
+            ReplayError::Version(version) => write!(
+                f,
+                "replay version {version} is not supported, expected {REPLAY_VERSION}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// The replay of the most recently finished run
+#[derive(Resource, Default, Deref)]
+pub struct LastReplay(pub Option<Replay>);
+
+/// The replay of the highest scoring run of every difficulty
+#[derive(Resource, Default, Deref)]
+pub struct PersonalBests(pub BTreeMap<Difficulty, Replay>);
+
+/// The run in progress
+#[derive(Resource)]
+struct Recording(Replay);
+
+/// Plays back a replay instead of reading the player's input.
+/// Removed again once the replayed run is over.
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    replay: Replay,
+    started: bool,
+    /// The play area and `passable_only` of the game, put back once the replay is over
+    previous: Option<(PlayArea, bool)>,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            started: false,
+            previous: None,
+        }
+    }
+}
+
+pub struct ReplayPlugin {
+    /// Replay to play back at startup
+    pub playback: Option<Replay>,
+    /// Write every finished run to `./last_replay.ron` and keep the personal best of every
+    /// difficulty in `./best_replay_<difficulty>.ron`
+    pub saving: bool,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LastReplay>()
+            .init_resource::<PersonalBests>()
+            .insert_resource(Recording(Replay::new(0, Difficulty::default())))
+            .add_systems(OnEnter(GameState::Playing), start_recording)
+            .add_systems(
+                OnEnter(GameState::Dead),
+                (
+                    finish_recording,
+                    // Someone else's replay, the autopilot's run or a course shouldn't become the
+                    // personal best
+                    update_personal_best.run_if(
+                        not(resource_exists::<ReplayPlayback>)
+                            .and(not(resource_exists::<AttractDemo>))
+                            .and(not(resource_exists::<Course>)),
+                    ),
+                    finish_playback.run_if(resource_exists::<ReplayPlayback>),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    (
+                        disable_player_input.run_if(resource_added::<ReplayPlayback>),
+                        play_back,
+                    )
+                        .chain()
+                        .after(GameplaySystems::Tick)
+                        .before(GameplaySystems::Input)
+                        .run_if(resource_exists::<ReplayPlayback>),
+                    record_jumps
+                        .in_set(GameplaySystems::Input)
+                        .run_if(in_state(GameState::Playing)),
+                    record_scores
+                        .after(GameplaySystems::Rules)
+                        .run_if(in_state(GameState::Playing)),
+                ),
+            );
+
+        if let Some(replay) = &self.playback {
+            app.insert_resource(ReplayPlayback::new(replay.clone()));
+        }
+
+        if self.saving {
+            app.add_systems(Startup, load_personal_best).add_systems(
+                OnEnter(GameState::Dead),
+                (
+                    // Replays don't know about courses
+                    save_last_replay
+                        .after(finish_recording)
+                        .run_if(not(resource_exists::<Course>)),
+                    save_personal_best
+                        .after(update_personal_best)
+                        .run_if(resource_changed::<PersonalBests>),
+                ),
+            );
+        }
+    }
+}
+
+fn start_recording(
+    mut recording: ResMut<Recording>,
+    run_seed: Res<RunSeed>,
+    difficulty: Res<Difficulty>,
+    assist: Res<DifficultyAssist>,
+    curve: ActiveDifficultyCurve,
+    play_area: Res<PlayArea>,
+    scene_settings: Res<SceneSettings>,
+) {
+    recording.0 = Replay {
+        assist: *assist,
+        curve: curve.unassisted().clone(),
+        play_area: play_area.clone(),
+        passable_only: scene_settings.passable_only,
+        ..Replay::new(**run_seed, *difficulty)
+    };
+}
+
+fn record_jumps(
+    action_state: Single<&ActionState<Action>, With<Player>>,
+    tick: Res<SimulationTick>,
+    mut recording: ResMut<Recording>,
+) {
+    if action_state.just_pressed(&Action::Jump) {
+        recording.0.jumps.push(**tick);
+    }
+}
+
+fn record_scores(
+    mut scored_event: MessageReader<ScoredEvent>,
+    score_info: Res<ScoreInfo>,
+    tick: Res<SimulationTick>,
+    mut recording: ResMut<Recording>,
+) {
+    if scored_event.read().count() > 0 {
+        recording
+            .0
+            .score_ticks
+            .push((**tick, score_info.total_score()));
+    }
+}
+
+fn finish_recording(
+    mut recording: ResMut<Recording>,
+    mut last_replay: ResMut<LastReplay>,
+    mut player_died: MessageReader<PlayerDied>,
+    score_info: Res<ScoreInfo>,
+    tick: Res<SimulationTick>,
+) {
+    recording.0.score = score_info.current_score;
+    recording.0.death_tick = **tick;
+    recording.0.death_cause = player_died.read().last().map(|died| died.cause);
+
+    last_replay.0 = Some(recording.0.clone());
+}
+
+fn save_last_replay(last_replay: Res<LastReplay>) {
+    if let Some(replay) = &**last_replay
+        && let Err(err) = replay.save(LAST_REPLAY_PATH)
+    {
+        warn!("Could not save replay: {err}");
+    }
+}
+
+fn update_personal_best(last_replay: Res<LastReplay>, mut personal_bests: ResMut<PersonalBests>) {
+    // Assisted runs don't compete with the others
+    let Some(last) = last_replay.0.as_ref().filter(|last| last.assist.is_none()) else {
+        return;
+    };
+
+    if personal_bests
+        .get(&last.difficulty)
+        .is_none_or(|best| last.score > best.score)
+    {
+        personal_bests.0.insert(last.difficulty, last.clone());
+    }
+}
+
+fn load_personal_best(mut personal_bests: ResMut<PersonalBests>) {
+    for difficulty in Difficulty::ALL {
+        match Replay::load(personal_best_path(difficulty)) {
+            Ok(replay) => {
+                personal_bests.0.insert(difficulty, replay);
+            }
+            Err(ReplayError::Io(_)) => {} // No personal best yet
+            Err(err) => warn!("Ignoring {difficulty} personal best replay: {err}"),
+        }
+    }
+}
+
+// Only the difficulty of the last run can have a new personal best
+fn save_personal_best(personal_bests: Res<PersonalBests>, last_replay: Res<LastReplay>) {
+    let Some(last) = &**last_replay else {
+        return;
+    };
+
+    if let Some(replay) = personal_bests.get(&last.difficulty)
+        && let Err(err) = replay.save(personal_best_path(last.difficulty))
+    {
+        warn!("Could not save personal best replay: {err}");
+    }
+}
+
+// Keep the player's own input from interfering while the replay plays
+fn disable_player_input(mut commands: Commands, player: Single<Entity, With<Player>>) {
+    commands.entity(*player).remove::<InputMap<Action>>();
+}
+
+fn play_back(
+    mut commands: Commands,
+    mut playback: ResMut<ReplayPlayback>,
+    mut difficulty: ResMut<Difficulty>,
+    mut assist: ResMut<DifficultyAssist>,
+    mut curves: ResMut<DifficultyCurves>,
+    mut play_area: ResMut<PlayArea>,
+    mut scene_settings: ResMut<SceneSettings>,
+    mut action_state: Single<&mut ActionState<Action>, With<Player>>,
+    mut run_seed: ResMut<RunSeed>,
+    state: Res<State<GameState>>,
+    tick: Res<SimulationTick>,
+) {
+    // Release after every press, a jump only registers when the action is newly pressed
+    if action_state.pressed(&Action::Jump) {
+        action_state.release(&Action::Jump);
+        return;
+    }
+
+    let press = match state.get() {
+        // The level has to be spawned with the replay's settings
+        GameState::Ready if playback.previous.is_none() => {
+            playback.previous = Some((play_area.clone(), scene_settings.passable_only));
+
+            let replay = &playback.replay;
+            *difficulty = replay.difficulty;
+            *assist = replay.assist;
+            curves.replayed = Some(replay.curve.clone());
+            *play_area = replay.play_area.clone();
+            scene_settings.passable_only = replay.passable_only;
+
+            commands.queue(ResetRun);
+            false
+        }
+        GameState::Ready if !playback.started => {
+            playback.started = true;
+            run_seed.0 = playback.replay.seed;
+            true
+        }
+        GameState::Playing => playback.replay.jumps.binary_search(&tick).is_ok(),
+        _ => false,
+    };
+
+    if press {
+        action_state.press(&Action::Jump);
+    }
+}
+
+fn finish_playback(
+    mut commands: Commands,
+    playback: Res<ReplayPlayback>,
+    mut curves: ResMut<DifficultyCurves>,
+    mut play_area: ResMut<PlayArea>,
+    mut scene_settings: ResMut<SceneSettings>,
+    player: Single<Entity, With<Player>>,
+    mut player_died: MessageReader<PlayerDied>,
+    score_info: Res<ScoreInfo>,
+    tick: Res<SimulationTick>,
+) {
+    let replay = &playback.replay;
+    let death_cause = player_died.read().last().map(|died| died.cause);
+
+    if replay.score == score_info.current_score
+        && replay.death_tick == **tick
+        && replay.death_cause == death_cause
+    {
+        info!(
+            "Replay reproduced: score {} on tick {}, {:?}",
+            replay.score, replay.death_tick, replay.death_cause
+        );
+    } else {
+        warn!(
+            "Replay diverged: recorded score {} on tick {} ({:?}), got score {} on tick {} ({:?})",
+            replay.score,
+            replay.death_tick,
+            replay.death_cause,
+            score_info.current_score,
+            **tick,
+            death_cause
+        );
+    }
+
+    curves.replayed = None;
+    if let Some((previous_play_area, passable_only)) = &playback.previous {
+        *play_area = previous_play_area.clone();
+        scene_settings.passable_only = *passable_only;
+    }
+
+    commands.remove_resource::<ReplayPlayback>();
+    commands.entity(*player).insert(create_input_map());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay() -> Replay {
+        Replay {
+            passable_only: true,
+            jumps: vec![10, 40, 75],
+            score_ticks: vec![(60, 1), (120, 4)],
+            score: 2,
+            death_tick: 150,
+            death_cause: Some(DeathCause::UpperPipe),
+            ..Replay::new(42, Difficulty::Hard)
+        }
+    }
+
+    fn to_ron(replay: &Replay) -> String {
+        ron::ser::to_string_pretty(replay, ron::ser::PrettyConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let replay = replay();
+
+        assert_eq!(Replay::from_ron(&to_ron(&replay)).unwrap(), replay);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let replay = Replay {
+            version: REPLAY_VERSION + 1,
+            ..replay()
+        };
+
+        assert!(matches!(
+            Replay::from_ron(&to_ron(&replay)),
+            Err(ReplayError::Version(version)) if version == REPLAY_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_settings() {
+        let data = to_ron(&replay()).replace("passable_only: true,", "");
+
+        assert!(matches!(
+            Replay::from_ron(&data),
+            Err(ReplayError::Parse(_))
+        ));
+    }
+}
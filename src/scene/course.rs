@@ -0,0 +1,271 @@
+//! Handcrafted courses with a finish line, as opposed to the endless random pipes.
+//!
+//! While a [`Course`] resource exists, every level is that course. Its pipes are placed once and
+//! not recycled, and passing the finish line ends the run with a [`CourseCompleted`] message.
+//! Course runs don't count for the high score or the personal best.
+
+use std::fmt;
+use std::path::Path;
+
+use avian3d::math::PI;
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::gameplay::{GameState, GameplaySystems, PlayArea, ScoreInfo, SimulationTick};
+use crate::player::Player;
+use crate::scene::SceneSettings;
+use crate::scene::pipes::{PipeBehaviour, SpawnPipePair};
+
+/// A fixed pipe layout, loaded from a `.course.ron` file
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Course {
+    pub name: String,
+    /// Sorted by `x` when loaded
+    pub pipes: Vec<CoursePipePair>,
+    /// Distance from the player to the finish line at the start of the run
+    pub finish_x: f32,
+    /// Replaces the [`PlayArea`] of the game while the course is played
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub play_area: Option<PlayArea>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CoursePipePair {
+    /// Distance from the player at the start of the run
+    pub x: f32,
+    /// Height of the bottom of the gap
+    pub y: f32,
+    pub gap_y: f32,
+    #[serde(default, skip_serializing_if = "PipeBehaviour::is_static")]
+    pub behaviour: PipeBehaviour,
+}
+
+impl Course {
+    pub fn from_ron(data: &str) -> Result<Self, CourseError> {
+        let mut course: Self = ron::from_str(data).map_err(CourseError::Parse)?;
+        course.pipes.sort_by(|a, b| a.x.total_cmp(&b.x));
+        course.check()?;
+
+        Ok(course)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CourseError> {
+        let data = std::fs::read_to_string(path).map_err(CourseError::Io)?;
+        Self::from_ron(&data)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CourseError> {
+        self.check()?;
+        let data = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(CourseError::Serialize)?;
+        std::fs::write(path, data).map_err(CourseError::Io)
+    }
+
+    /// Checks that the course can be played, the pipes are expected to be sorted
+    pub fn check(&self) -> Result<(), CourseError> {
+        match self.pipes.last() {
+            Some(last_pipe) if last_pipe.x >= self.finish_x => {
+                Err(CourseError::FinishBeforePipe(last_pipe.x))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CourseError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    /// The finish line has to come after every pipe
+    FinishBeforePipe(f32),
+}
+
+impl fmt::Display for CourseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CourseError::Io(err) => write!(f, "{err}"),
+            CourseError::Parse(err) => write!(f, "invalid course: {err}"),
+            CourseError::Serialize(err) => write!(f, "could not serialize course: {err}"),
+            CourseError::FinishBeforePipe(x) => {
+                write!(f, "the finish line comes before the pipe at x = {x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CourseError {}
+
+/// Scrolls towards the player like the pipes, the course is completed when it reaches them
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct FinishLine;
+
+/// The player crossed the [`FinishLine`] of the [`Course`]
+#[derive(Message, Debug, Clone, Copy, PartialEq)]
+pub struct CourseCompleted {
+    /// Time since the run started, in simulated seconds
+    pub seconds: f32,
+    pub score: u32,
+}
+
+pub(super) struct CoursePlugin {
+    /// Give the finish line a mesh
+    pub rendering: bool,
+}
+
+impl Plugin for CoursePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<FinishLine>()
+            .add_message::<CourseCompleted>()
+            .add_systems(OnEnter(GameState::Finished), stop_player)
+            .add_systems(
+                FixedUpdate,
+                complete_course
+                    .in_set(GameplaySystems::Rules)
+                    .run_if(in_state(GameState::Playing).and(resource_exists::<Course>)),
+            );
+
+        if self.rendering {
+            app.add_systems(Update, attach_finish_line_mesh);
+        }
+    }
+}
+
+pub(crate) fn spawn_course(commands: &mut Commands, course: &Course) {
+    for (i, pipe) in course.pipes.iter().enumerate() {
+        commands.queue(SpawnPipePair {
+            position_x: pipe.x,
+            height: pipe.y,
+            gap_y: pipe.gap_y,
+            rotation: i as f32 * PI * 0.5,
+            behaviour: pipe.behaviour,
+            pickup: None,
+        });
+    }
+
+    commands.spawn((
+        Name::from("FinishLine"),
+        FinishLine,
+        Transform::from_xyz(course.finish_x, 0.0, 0.0),
+        Visibility::default(),
+    ));
+}
+
+fn complete_course(
+    finish_line: Single<&Transform, With<FinishLine>>,
+    player: Single<&Transform, With<Player>>,
+    mut scene_settings: ResMut<SceneSettings>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut course_completed: MessageWriter<CourseCompleted>,
+    course: Res<Course>,
+    score_info: Res<ScoreInfo>,
+    tick: Res<SimulationTick>,
+    time: Res<Time<Fixed>>,
+) {
+    if finish_line.translation.x > player.translation.x {
+        return;
+    }
+
+    let completed = CourseCompleted {
+        seconds: **tick as f32 * time.timestep().as_secs_f32(),
+        score: score_info.current_score,
+    };
+    info!(
+        "Completed {:?} in {:.2}s with {} points",
+        course.name, completed.seconds, completed.score
+    );
+    course_completed.write(completed);
+
+    scene_settings.pipe_speed = 0.0;
+    next_state.set(GameState::Finished);
+}
+
+// Keep the bird where it crossed the line until the next run
+fn stop_player(mut commands: Commands, player: Single<Entity, With<Player>>) {
+    commands.entity(*player).insert((
+        LockedAxes::ALL_LOCKED,
+        LinearVelocity::ZERO,
+        AngularVelocity::ZERO,
+    ));
+}
+
+fn attach_finish_line_mesh(
+    mut commands: Commands,
+    finish_line_query: Query<Entity, (With<FinishLine>, Without<Mesh3d>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for finish_line in &finish_line_query {
+        commands.entity(finish_line).insert((
+            Mesh3d(meshes.add(Cuboid::new(0.2, 40.0, 2.0))),
+            MeshMaterial3d(materials.add(StandardMaterial {
+                base_color: Color::WHITE,
+                unlit: true,
+                ..default()
+            })),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_ron_sorts_the_pipes() {
+        let course = Course::from_ron(
+            "(
+                name: \"Test\",
+                pipes: [
+                    (x: 14.0, y: 1.0, gap_y: 3.0),
+                    (x: 7.0, y: -0.5, gap_y: 3.0, behaviour: Oscillate(amplitude: 1.0, period: 2.0)),
+                ],
+                finish_x: 21.0,
+            )",
+        )
+        .unwrap();
+
+        assert_eq!(course.name, "Test");
+        assert_eq!(course.pipes[0].x, 7.0);
+        assert_eq!(
+            course.pipes[0].behaviour,
+            PipeBehaviour::Oscillate {
+                amplitude: 1.0,
+                period: 2.0
+            }
+        );
+        assert!(course.pipes[1].behaviour.is_static());
+        assert_eq!(course.play_area, None);
+    }
+
+    #[test]
+    fn from_ron_checks_the_finish_line() {
+        let result = Course::from_ron(
+            "(name: \"Test\", pipes: [(x: 14.0, y: 1.0, gap_y: 3.0)], finish_x: 14.0)",
+        );
+
+        assert!(matches!(result, Err(CourseError::FinishBeforePipe(14.0))));
+    }
+
+    #[test]
+    fn course_without_pipes() {
+        let course = Course {
+            name: "Empty".to_string(),
+            pipes: Vec::new(),
+            finish_x: 0.0,
+            play_area: None,
+        };
+
+        assert!(course.check().is_ok());
+    }
+
+    #[test]
+    fn invalid_course() {
+        assert!(matches!(
+            Course::from_ron("(name: \"Test\")"),
+            Err(CourseError::Parse(_))
+        ));
+    }
+}
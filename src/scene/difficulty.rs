@@ -0,0 +1,523 @@
+//! How the pipes change over the course of a run.
+//!
+//! Every [`Difficulty`] preset follows its own [`DifficultyCurve`] asset, loaded from
+//! `assets/difficulty/`. The files are watched for changes when the `hot_reload` feature is
+//! enabled, so the curves can be tuned while the game is running.
+
+use std::fmt;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::gameplay::{GameState, GameplaySystems, ScoreInfo, SimulationTick};
+use crate::scene::{SceneSettings, move_pipes, spawn_level};
+
+/// The preset chosen before a run. Every preset has its own high score.
+#[derive(
+    Resource,
+    Reflect,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Deserialize,
+    Serialize,
+)]
+#[reflect(Resource)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    /// Top speed from the start, and the pipes are always as high or as low as they go
+    Max,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Normal,
+        Difficulty::Hard,
+        Difficulty::Max,
+    ];
+
+    /// The next preset, wrapping around after [`Difficulty::Max`]
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Max,
+            Difficulty::Max => Difficulty::Easy,
+        }
+    }
+
+    fn curve_path(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "difficulty/easy.difficulty.ron",
+            Difficulty::Normal => "difficulty/normal.difficulty.ron",
+            Difficulty::Hard => "difficulty/hard.difficulty.ron",
+            Difficulty::Max => "difficulty/max.difficulty.ron",
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+            Difficulty::Max => "Max",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// What the keyframes of a [`DifficultyCurve`] are placed along
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum DifficultyKey {
+    /// Seconds since the run started
+    #[default]
+    Time,
+    /// Points scored during the run
+    Score,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct DifficultyKeyframe {
+    /// Seconds or score, depending on the [`DifficultyKey`]
+    pub at: f32,
+    pub pipe_speed: f32,
+    /// Horizontal distance to the previous pipe, applies as pipes are spawned or recycled
+    pub pipe_gap_x: f32,
+    /// Height of the gap, applies as pipes are spawned or recycled
+    pub pipe_gap_y: f32,
+    pub pipe_spread: f32,
+    /// Chance for a recycled pipe pair to move, see [`PipeBehaviour`](crate::scene::pipes::PipeBehaviour)
+    #[serde(default)]
+    pub pipe_behaviour_chance: f32,
+    /// Chance for a recycled pipe pair to carry a [`Pickup`](crate::scene::pickups::Pickup)
+    #[serde(default)]
+    pub pickup_chance: f32,
+}
+
+/// Pipe settings at points of a run, linearly interpolated in between.
+/// Before the first keyframe and after the last one the values stay the same.
+#[derive(Asset, TypePath, Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DifficultyCurve {
+    #[serde(default)]
+    pub key: DifficultyKey,
+    /// What `pipe_speed` is placed along instead of `key`, if set. This way the speed can ramp up
+    /// over time while the pipes get narrower with the score.
+    #[serde(default)]
+    pub speed_key: Option<DifficultyKey>,
+    pub keyframes: Vec<DifficultyKeyframe>,
+    /// Only place pipes as high or as low as the spread allows
+    #[serde(default)]
+    pub extreme_heights: bool,
+}
+
+impl Default for DifficultyCurve {
+    fn default() -> Self {
+        Self::preset(Difficulty::default())
+    }
+}
+
+impl DifficultyCurve {
+    /// Sorts the keyframes, which may be given in any order. Fails without keyframes.
+    pub fn validate(mut self) -> Result<Self, DifficultyCurveError> {
+        if self.keyframes.is_empty() {
+            return Err(DifficultyCurveError::NoKeyframes);
+        }
+        self.keyframes.sort_by(|a, b| a.at.total_cmp(&b.at));
+
+        Ok(self)
+    }
+
+    /// The built-in curve of a preset, its asset file as it was at compile time
+    pub fn preset(difficulty: Difficulty) -> Self {
+        let data = match difficulty {
+            Difficulty::Easy => include_str!("../../assets/difficulty/easy.difficulty.ron"),
+            Difficulty::Normal => include_str!("../../assets/difficulty/normal.difficulty.ron"),
+            Difficulty::Hard => include_str!("../../assets/difficulty/hard.difficulty.ron"),
+            Difficulty::Max => include_str!("../../assets/difficulty/max.difficulty.ron"),
+        };
+
+        ron::from_str::<Self>(data)
+            .map_err(DifficultyCurveError::Parse)
+            .and_then(Self::validate)
+            .unwrap_or_else(|err| panic!("built-in {difficulty} difficulty curve: {err}"))
+    }
+
+    /// The interpolated values at `at`, in seconds or score depending on the [`DifficultyKey`]
+    pub fn sample(&self, at: f32) -> DifficultyKeyframe {
+        let next = self.keyframes.partition_point(|keyframe| keyframe.at <= at);
+        let previous = next
+            .checked_sub(1)
+            .and_then(|index| self.keyframes.get(index));
+
+        match (previous, self.keyframes.get(next)) {
+            (Some(previous), Some(next)) => {
+                let t = (at - previous.at) / (next.at - previous.at);
+                DifficultyKeyframe {
+                    at,
+                    pipe_speed: previous.pipe_speed.lerp(next.pipe_speed, t),
+                    pipe_gap_x: previous.pipe_gap_x.lerp(next.pipe_gap_x, t),
+                    pipe_gap_y: previous.pipe_gap_y.lerp(next.pipe_gap_y, t),
+                    pipe_spread: previous.pipe_spread.lerp(next.pipe_spread, t),
+                    pipe_behaviour_chance: previous
+                        .pipe_behaviour_chance
+                        .lerp(next.pipe_behaviour_chance, t),
+                    pickup_chance: previous.pickup_chance.lerp(next.pickup_chance, t),
+                }
+            }
+            (Some(keyframe), None) | (None, Some(keyframe)) => *keyframe,
+            // Loading rejects curves without keyframes
+            (None, None) => DifficultyCurve::default().sample(at),
+        }
+    }
+
+    /// The values `tick` fixed timesteps into a run with `score` points
+    pub fn sample_run(&self, tick: u64, timestep: f32, score: u32) -> DifficultyKeyframe {
+        let at = |key| match key {
+            DifficultyKey::Time => tick as f32 * timestep,
+            DifficultyKey::Score => score as f32,
+        };
+
+        let mut values = self.sample(at(self.key));
+        if let Some(speed_key) = self.speed_key {
+            values.pipe_speed = self.sample(at(speed_key)).pipe_speed;
+        }
+        values
+    }
+}
+
+/// Makes the pipes easier than the [`DifficultyCurve`] says, handed out by the
+/// [`director`](super::director) to players who keep dying early
+#[derive(Resource, Reflect, Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[reflect(Resource)]
+pub struct DifficultyAssist {
+    /// Added to the vertical gap
+    pub gap_bonus: f32,
+    /// Multiplies the spread of the pipe heights
+    pub spread_scale: f32,
+    /// Multiplies the time or score at which every keyframe is reached, slowing down the ramp
+    pub ramp_stretch: f32,
+}
+
+impl Default for DifficultyAssist {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+impl DifficultyAssist {
+    pub const NONE: Self = Self {
+        gap_bonus: 0.0,
+        spread_scale: 1.0,
+        ramp_stretch: 1.0,
+    };
+
+    pub fn is_none(&self) -> bool {
+        *self == Self::NONE
+    }
+
+    pub fn apply(&self, curve: &DifficultyCurve) -> DifficultyCurve {
+        let keyframes = curve
+            .keyframes
+            .iter()
+            .map(|keyframe| DifficultyKeyframe {
+                at: keyframe.at * self.ramp_stretch,
+                pipe_gap_y: keyframe.pipe_gap_y + self.gap_bonus,
+                pipe_spread: keyframe.pipe_spread * self.spread_scale,
+                ..*keyframe
+            })
+            .collect();
+
+        DifficultyCurve {
+            keyframes,
+            ..curve.clone()
+        }
+    }
+}
+
+/// The [`DifficultyCurve`] of every preset
+#[derive(Resource)]
+pub struct DifficultyCurves {
+    /// The loaded assets, by preset
+    handles: Option<[Handle<DifficultyCurve>; 4]>,
+    /// Used until the assets have loaded, by preset
+    fallbacks: [DifficultyCurve; 4],
+    /// Used on every preset while a [`Replay`](crate::replay::Replay) plays, it brings its own
+    pub(crate) replayed: Option<DifficultyCurve>,
+}
+
+impl DifficultyCurves {
+    pub fn get<'a>(
+        &'a self,
+        difficulty: Difficulty,
+        curves: &'a Assets<DifficultyCurve>,
+    ) -> &'a DifficultyCurve {
+        if let Some(replayed) = &self.replayed {
+            return replayed;
+        }
+
+        let index = difficulty as usize;
+        self.handles
+            .as_ref()
+            .and_then(|handles| curves.get(&handles[index]))
+            .unwrap_or(&self.fallbacks[index])
+    }
+}
+
+/// The curve of the chosen [`Difficulty`], with the [`DifficultyAssist`] applied
+#[derive(SystemParam)]
+pub struct ActiveDifficultyCurve<'w> {
+    difficulty: Res<'w, Difficulty>,
+    assist: Res<'w, DifficultyAssist>,
+    curves: Res<'w, DifficultyCurves>,
+    assets: Res<'w, Assets<DifficultyCurve>>,
+}
+
+impl ActiveDifficultyCurve<'_> {
+    pub fn get(&self) -> DifficultyCurve {
+        self.assist.apply(self.unassisted())
+    }
+
+    /// The curve of the chosen [`Difficulty`] as it is
+    pub fn unassisted(&self) -> &DifficultyCurve {
+        self.curves.get(*self.difficulty, &self.assets)
+    }
+}
+
+pub(super) struct DifficultyPlugin {
+    /// Use this curve for every preset instead of loading them
+    pub curve: Option<DifficultyCurve>,
+    pub load_assets: bool,
+}
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<DifficultyCurve>()
+            .register_asset_loader(DifficultyCurveLoader)
+            .register_type::<Difficulty>()
+            .register_type::<DifficultyAssist>()
+            .init_resource::<Difficulty>()
+            .init_resource::<DifficultyAssist>();
+
+        let handles = (self.load_assets && self.curve.is_none()).then(|| {
+            let asset_server = app.world().resource::<AssetServer>();
+            Difficulty::ALL.map(|difficulty| asset_server.load(difficulty.curve_path()))
+        });
+
+        let fallbacks = Difficulty::ALL.map(|difficulty| {
+            self.curve
+                .clone()
+                .unwrap_or_else(|| DifficultyCurve::preset(difficulty))
+        });
+
+        app.insert_resource(DifficultyCurves {
+            handles,
+            fallbacks,
+            replayed: None,
+        })
+        .add_systems(
+            OnEnter(GameState::Ready),
+            apply_starting_difficulty.before(spawn_level),
+        )
+        .add_systems(
+            FixedUpdate,
+            apply_difficulty
+                .in_set(GameplaySystems::Movement)
+                .before(move_pipes)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(Update, log_reloads);
+    }
+}
+
+// The level is spawned with the values at the start of a run
+fn apply_starting_difficulty(
+    mut scene_settings: ResMut<SceneSettings>,
+    curve: ActiveDifficultyCurve,
+    time: Res<Time<Fixed>>,
+) {
+    let values = curve.get().sample_run(0, time.timestep().as_secs_f32(), 0);
+
+    scene_settings.pipe_gap_x = values.pipe_gap_x;
+    scene_settings.pipe_gap_y = values.pipe_gap_y;
+    scene_settings.pipe_spread = values.pipe_spread;
+    scene_settings.pipe_behaviour_chance = values.pipe_behaviour_chance;
+    scene_settings.pickup_chance = values.pickup_chance;
+}
+
+pub(crate) fn apply_difficulty(
+    mut scene_settings: ResMut<SceneSettings>,
+    curve: ActiveDifficultyCurve,
+    tick: Res<SimulationTick>,
+    score_info: Res<ScoreInfo>,
+    time: Res<Time>,
+) {
+    let values = curve
+        .get()
+        .sample_run(**tick, time.delta_secs(), score_info.current_score);
+
+    scene_settings.pipe_speed = values.pipe_speed;
+    scene_settings.pipe_gap_x = values.pipe_gap_x;
+    scene_settings.pipe_gap_y = values.pipe_gap_y;
+    scene_settings.pipe_spread = values.pipe_spread;
+    scene_settings.pipe_behaviour_chance = values.pipe_behaviour_chance;
+    scene_settings.pickup_chance = values.pickup_chance;
+}
+
+fn log_reloads(
+    mut asset_events: MessageReader<AssetEvent<DifficultyCurve>>,
+    curves: Res<DifficultyCurves>,
+) {
+    let Some(handles) = &curves.handles else {
+        return;
+    };
+
+    for event in asset_events.read() {
+        for (difficulty, handle) in Difficulty::ALL.iter().zip(handles) {
+            if event.is_modified(handle) {
+                info!("Reloaded the {difficulty} difficulty curve");
+            }
+        }
+    }
+}
+
+#[derive(Default, TypePath)]
+struct DifficultyCurveLoader;
+
+#[derive(Debug)]
+pub enum DifficultyCurveError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    NoKeyframes,
+}
+
+impl fmt::Display for DifficultyCurveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DifficultyCurveError::Io(err) => write!(f, "{err}"),
+            DifficultyCurveError::Parse(err) => write!(f, "invalid difficulty curve: {err}"),
+            DifficultyCurveError::NoKeyframes => {
+                write!(f, "a difficulty curve needs at least one keyframe")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DifficultyCurveError {}
+
+impl AssetLoader for DifficultyCurveLoader {
+    type Asset = DifficultyCurve;
+    type Settings = ();
+    type Error = DifficultyCurveError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<DifficultyCurve, DifficultyCurveError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(DifficultyCurveError::Io)?;
+
+        ron::de::from_bytes::<DifficultyCurve>(&bytes)
+            .map_err(DifficultyCurveError::Parse)?
+            .validate()
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["difficulty.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_match_the_assets() {
+        for difficulty in Difficulty::ALL {
+            let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("assets")
+                .join(difficulty.curve_path());
+            let data = std::fs::read_to_string(path).unwrap();
+            let asset = ron::from_str::<DifficultyCurve>(&data)
+                .unwrap()
+                .validate()
+                .unwrap();
+
+            assert_eq!(DifficultyCurve::preset(difficulty), asset, "{difficulty}");
+        }
+    }
+
+    #[test]
+    fn sample_interpolates_between_keyframes() {
+        let curve = DifficultyCurve::preset(Difficulty::Normal);
+
+        let values = curve.sample(7.5);
+        assert_eq!(values.at, 7.5);
+        assert_eq!(values.pipe_speed, 6.5);
+        assert_eq!(values.pipe_gap_y, 3.1);
+    }
+
+    #[test]
+    fn sample_holds_the_first_and_last_keyframe() {
+        let curve = DifficultyCurve::preset(Difficulty::Normal);
+
+        assert_eq!(curve.sample(-1.0), curve.keyframes[0]);
+        assert_eq!(curve.sample(100.0), curve.keyframes[2]);
+    }
+
+    #[test]
+    fn sample_run_places_the_speed_along_time() {
+        let curve = DifficultyCurve::preset(Difficulty::Normal);
+
+        // 15 seconds in with no score, the pipes are at full speed but still wide
+        let values = curve.sample_run(15 * 64, 1.0 / 64.0, 0);
+        assert_eq!(values.pipe_speed, 8.0);
+        assert_eq!(values.pipe_gap_y, 3.1);
+
+        // The other way around
+        let values = curve.sample_run(0, 1.0 / 64.0, 50);
+        assert_eq!(values.pipe_speed, 5.0);
+        assert_eq!(values.pipe_gap_y, 2.8);
+    }
+
+    #[test]
+    fn validate_sorts_the_keyframes() {
+        let mut curve = DifficultyCurve::preset(Difficulty::Hard);
+        curve.keyframes.reverse();
+
+        let curve = curve.validate().unwrap();
+        assert!(curve.keyframes.is_sorted_by(|a, b| a.at <= b.at));
+    }
+
+    #[test]
+    fn validate_rejects_no_keyframes() {
+        let curve = DifficultyCurve {
+            keyframes: Vec::new(),
+            ..DifficultyCurve::preset(Difficulty::Normal)
+        };
+
+        assert!(matches!(
+            curve.validate(),
+            Err(DifficultyCurveError::NoKeyframes)
+        ));
+    }
+}
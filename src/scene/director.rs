@@ -0,0 +1,291 @@
+//! Eases the pipes for players who keep dying early.
+//!
+//! After every run the director looks at the last few runs: how long they lasted and where the
+//! bird died relative to the gap. If the player is struggling it hands out a bit more
+//! [`DifficultyAssist`], and it takes it back again once they do well. Every decision is logged.
+//!
+//! The daily challenge is ranked, so it's never assisted. Assisted runs don't count for the high
+//! score or the personal best either.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::gameplay::{DeathCause, GameState, PlayerDied};
+use crate::player::Player;
+use crate::player::autopilot::AttractDemo;
+use crate::replay::ReplayPlayback;
+use crate::scene::SeedMode;
+use crate::scene::course::Course;
+use crate::scene::difficulty::DifficultyAssist;
+use crate::scene::pipes::PipePair;
+
+/// Number of recent runs the decisions are based on
+const RUN_WINDOW: usize = 5;
+/// Runs needed before making a decision
+const MIN_RUNS: usize = 3;
+/// Players with a median score below this get more help
+const STRUGGLING_SCORE: u32 = 3;
+/// Players with a median score of at least this get less help
+const CONFIDENT_SCORE: u32 = 10;
+/// Runs shorter than this end while the pipes are still speeding up
+const RAMP_SECONDS: f32 = 15.0;
+
+const GAP_BONUS_STEP: f32 = 0.1;
+const MAX_GAP_BONUS: f32 = 0.6;
+const SPREAD_SCALE_STEP: f32 = 0.1;
+const MIN_SPREAD_SCALE: f32 = 0.6;
+const RAMP_STRETCH_STEP: f32 = 0.25;
+const MAX_RAMP_STRETCH: f32 = 2.0;
+
+/// How a run went, as far as the director cares
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunSummary {
+    pub score: u32,
+    pub seconds: f32,
+    pub cause: DeathCause,
+    /// Height of the bird above the middle of the nearest gap when it died
+    pub gap_offset: f32,
+    /// Whether the bird died close enough to the gap to have just clipped its edge
+    pub near_gap: bool,
+}
+
+/// The recent runs and the assist handed out for them
+#[derive(Resource, Debug, Default)]
+pub struct Director {
+    runs: VecDeque<RunSummary>,
+    assist: DifficultyAssist,
+}
+
+impl Director {
+    pub fn runs(&self) -> impl Iterator<Item = &RunSummary> {
+        self.runs.iter()
+    }
+
+    pub fn assist(&self) -> DifficultyAssist {
+        self.assist
+    }
+
+    fn record(&mut self, run: RunSummary) {
+        if self.runs.len() == RUN_WINDOW {
+            self.runs.pop_front();
+        }
+        self.runs.push_back(run);
+    }
+
+    // Changes the assist if the recent runs call for it, returns what was done
+    fn decide(&mut self) -> Option<String> {
+        if self.runs.len() < MIN_RUNS {
+            return None;
+        }
+
+        let mut scores: Vec<u32> = self.runs.iter().map(|run| run.score).collect();
+        scores.sort_unstable();
+        let median_score = scores[scores.len() / 2];
+
+        let mut seconds: Vec<f32> = self.runs.iter().map(|run| run.seconds).collect();
+        seconds.sort_unstable_by(f32::total_cmp);
+        let median_seconds = seconds[seconds.len() / 2];
+
+        let near_gap = self.runs.iter().filter(|run| run.near_gap).count();
+        let summary = format!(
+            "median score {median_score} and {median_seconds:.1}s over {} runs, \
+             {near_gap} deaths near the gap",
+            self.runs.len()
+        );
+
+        let previous = self.assist;
+        let assist = &mut self.assist;
+
+        if median_score < STRUGGLING_SCORE {
+            // Clipping the edge of the gap calls for a bigger gap, missing it entirely for gaps
+            // that are closer in height
+            if near_gap * 2 > self.runs.len() {
+                assist.gap_bonus = (assist.gap_bonus + GAP_BONUS_STEP).min(MAX_GAP_BONUS);
+            } else {
+                assist.spread_scale =
+                    (assist.spread_scale - SPREAD_SCALE_STEP).max(MIN_SPREAD_SCALE);
+            }
+
+            if median_seconds < RAMP_SECONDS {
+                assist.ramp_stretch =
+                    (assist.ramp_stretch + RAMP_STRETCH_STEP).min(MAX_RAMP_STRETCH);
+            }
+        } else if median_score >= CONFIDENT_SCORE {
+            assist.gap_bonus = (assist.gap_bonus - GAP_BONUS_STEP).max(0.0);
+            assist.spread_scale = (assist.spread_scale + SPREAD_SCALE_STEP).min(1.0);
+            assist.ramp_stretch = (assist.ramp_stretch - RAMP_STRETCH_STEP).max(1.0);
+        } else {
+            return Some(format!("{summary}, keeping {previous:?}"));
+        }
+
+        if self.assist == previous {
+            return Some(format!("{summary}, already at {previous:?}"));
+        }
+
+        // Judge the new assist on new runs only
+        self.runs.clear();
+
+        Some(format!(
+            "{summary}, changing {previous:?} to {:?}",
+            self.assist
+        ))
+    }
+}
+
+pub(crate) struct DirectorPlugin;
+
+impl Plugin for DirectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Director>().add_systems(
+            OnEnter(GameState::Dead),
+            (
+                record_run.run_if(
+                    not(resource_exists::<ReplayPlayback>)
+                        .and(not(resource_exists::<AttractDemo>))
+                        .and(not(resource_exists::<Course>))
+                        .and(not(is_ranked)),
+                ),
+                hand_out_assist,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn is_ranked(seed_mode: Res<SeedMode>) -> bool {
+    *seed_mode == SeedMode::Daily
+}
+
+fn record_run(
+    mut director: ResMut<Director>,
+    player: Single<&Transform, With<Player>>,
+    pipe_query: Query<(&Transform, &PipePair)>,
+    mut player_died: MessageReader<PlayerDied>,
+) {
+    let Some(died) = player_died.read().last() else {
+        return;
+    };
+
+    let nearest_pipe = pipe_query
+        .iter()
+        .min_by(|(a, _), (b, _)| a.translation.x.abs().total_cmp(&b.translation.x.abs()));
+    let half_gap = nearest_pipe.map_or(0.0, |(_, pipe)| pipe.gap_y / 2.0);
+    let gap_offset = nearest_pipe.map_or(0.0, |(transform, _)| {
+        player.translation.y - (transform.translation.y + half_gap)
+    });
+
+    let run = RunSummary {
+        score: died.score,
+        seconds: died.seconds,
+        cause: died.cause,
+        gap_offset,
+        near_gap: gap_offset.abs() < half_gap + 0.5,
+    };
+    director.record(run);
+
+    if let Some(decision) = director.decide() {
+        info!("Director: {decision}");
+    }
+}
+
+fn hand_out_assist(
+    director: Res<Director>,
+    mut assist: ResMut<DifficultyAssist>,
+    seed_mode: Res<SeedMode>,
+) {
+    *assist = if is_ranked(seed_mode) {
+        DifficultyAssist::NONE
+    } else {
+        director.assist
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(score: u32, seconds: f32, near_gap: bool) -> RunSummary {
+        RunSummary {
+            score,
+            seconds,
+            cause: DeathCause::LowerPipe,
+            gap_offset: 0.0,
+            near_gap,
+        }
+    }
+
+    fn director(runs: &[RunSummary]) -> Director {
+        let mut director = Director::default();
+        for run in runs {
+            director.record(*run);
+        }
+        director
+    }
+
+    #[test]
+    fn waits_for_enough_runs() {
+        let mut director = director(&[run(0, 3.0, true); MIN_RUNS - 1]);
+
+        assert!(director.decide().is_none());
+        assert_eq!(director.assist(), DifficultyAssist::NONE);
+    }
+
+    #[test]
+    fn clipping_the_gap_makes_it_bigger() {
+        let mut director = director(&[run(1, 20.0, true); MIN_RUNS]);
+
+        assert!(director.decide().is_some());
+        assert_eq!(
+            director.assist(),
+            DifficultyAssist {
+                gap_bonus: GAP_BONUS_STEP,
+                ..DifficultyAssist::NONE
+            }
+        );
+        assert_eq!(director.runs().count(), 0);
+    }
+
+    #[test]
+    fn missing_the_gap_early_brings_the_pipes_closer_and_slows_the_ramp() {
+        let mut director = director(&[run(1, 5.0, false); MIN_RUNS]);
+
+        director.decide();
+        assert_eq!(
+            director.assist(),
+            DifficultyAssist {
+                spread_scale: 1.0 - SPREAD_SCALE_STEP,
+                ramp_stretch: 1.0 + RAMP_STRETCH_STEP,
+                ..DifficultyAssist::NONE
+            }
+        );
+    }
+
+    #[test]
+    fn backs_off_for_confident_players() {
+        let mut director = director(&[run(1, 20.0, true); MIN_RUNS]);
+        director.decide();
+        for _ in 0..MIN_RUNS {
+            director.record(run(CONFIDENT_SCORE, 40.0, false));
+        }
+
+        director.decide();
+        assert_eq!(director.assist(), DifficultyAssist::NONE);
+    }
+
+    #[test]
+    fn keeps_the_assist_in_between() {
+        let mut director = director(&[run(5, 20.0, false); MIN_RUNS]);
+
+        director.decide();
+        assert_eq!(director.assist(), DifficultyAssist::NONE);
+        assert_eq!(director.runs().count(), MIN_RUNS);
+    }
+
+    #[test]
+    fn keeps_only_the_recent_runs() {
+        let director = director(&[run(1, 5.0, false); RUN_WINDOW + 2]);
+
+        assert_eq!(director.runs().count(), RUN_WINDOW);
+    }
+}
@@ -0,0 +1,167 @@
+//! Picks where the pipes go.
+//!
+//! The level asks the [`ObstacleSource`] for its first pipes, and for a new obstacle every time a
+//! pipe pair is recycled. Insert your own [`ObstacleSource`] to swap the [`RandomObstacles`] for
+//! patterns, handcrafted sequences or test fixtures:
+//!
+//! ```no_run
+//! # use bevy::prelude::*;
+//! # use bevy_bird::BevyBirdPlugin;
+//! # use bevy_bird::scene::generator::*;
+//! struct Stairs(f32);
+//!
+//! impl ObstacleGenerator for Stairs {
+//!     fn next(&mut self, context: &mut GeneratorContext) -> Obstacle {
+//!         self.0 = if self.0 > 1.0 { -2.0 } else { self.0 + 0.5 };
+//!         Obstacle {
+//!             height: self.0,
+//!             ..Obstacle::from_settings(context.settings)
+//!         }
+//!     }
+//! }
+//!
+//! App::new()
+//!     .add_plugins(DefaultPlugins)
+//!     .add_plugins(BevyBirdPlugin::default())
+//!     .insert_resource(ObstacleSource::new(Stairs(-2.0)))
+//!     .run();
+//! ```
+//!
+//! Replays only reproduce with the generator they were recorded with.
+
+use bevy::prelude::*;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+
+use crate::scene::SceneSettings;
+use crate::scene::difficulty::DifficultyCurve;
+use crate::scene::pickups::PickupKind;
+use crate::scene::pipes::PipeBehaviour;
+
+/// Number of pipe pairs in a level when the generator doesn't say otherwise
+pub const DEFAULT_PIPE_PAIRS: usize = 5;
+
+/// A pipe pair picked by an [`ObstacleGenerator`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obstacle {
+    /// Horizontal distance to the previous obstacle, or to the player for the first one
+    pub spacing: f32,
+    /// Height of the bottom of the gap
+    pub height: f32,
+    pub gap_y: f32,
+    pub behaviour: PipeBehaviour,
+    /// A power-up in the middle of the gap
+    pub pickup: Option<PickupKind>,
+}
+
+impl Obstacle {
+    /// A static pipe pair at height 0 with the spacing and gap of the settings, without a pickup
+    pub fn from_settings(settings: &SceneSettings) -> Self {
+        Self {
+            spacing: settings.pipe_gap_x,
+            height: 0.0,
+            gap_y: settings.pipe_gap_y,
+            behaviour: PipeBehaviour::Static,
+            pickup: None,
+        }
+    }
+}
+
+/// What a generator can base the next obstacle on
+pub struct GeneratorContext<'a> {
+    /// Seeded with the [`RunSeed`](crate::scene::RunSeed), draw from this for reproducible layouts
+    pub rng: &'a mut WyRand,
+    /// The pipe settings at this point of the run, sampled from the difficulty curve
+    pub settings: &'a SceneSettings,
+    /// The curve of the chosen difficulty, including the assist
+    pub curve: &'a DifficultyCurve,
+}
+
+pub trait ObstacleGenerator: Send + Sync + 'static {
+    /// The pipe pairs of a new level, nearest first. The same pairs are recycled for the rest of
+    /// the run.
+    ///
+    /// This happens when entering [`GameState::Ready`](crate::GameState::Ready), before the run
+    /// is seeded, so it shouldn't be random.
+    fn start(&mut self, settings: &SceneSettings) -> Vec<Obstacle> {
+        vec![Obstacle::from_settings(settings); DEFAULT_PIPE_PAIRS]
+    }
+
+    /// The obstacle after the furthest one, when a pipe pair has gone past the player.
+    ///
+    /// With [`SceneSettings::passable_only`] this is asked again for obstacles the solver can't
+    /// pass, and the behaviour and pickup are left out.
+    fn next(&mut self, context: &mut GeneratorContext) -> Obstacle;
+}
+
+/// The generator the level uses. Defaults to [`RandomObstacles`].
+#[derive(Resource)]
+pub struct ObstacleSource {
+    generator: Box<dyn ObstacleGenerator>,
+}
+
+impl ObstacleSource {
+    pub fn new(generator: impl ObstacleGenerator) -> Self {
+        Self {
+            generator: Box::new(generator),
+        }
+    }
+
+    pub(super) fn start(&mut self, settings: &SceneSettings) -> Vec<Obstacle> {
+        self.generator.start(settings)
+    }
+
+    pub(super) fn next(&mut self, context: &mut GeneratorContext) -> Obstacle {
+        self.generator.next(context)
+    }
+}
+
+impl Default for ObstacleSource {
+    fn default() -> Self {
+        Self::new(RandomObstacles)
+    }
+}
+
+/// Pipes at a random height within the spread, some of them moving or carrying a pickup as the
+/// curve allows
+pub struct RandomObstacles;
+
+impl ObstacleGenerator for RandomObstacles {
+    fn next(&mut self, context: &mut GeneratorContext) -> Obstacle {
+        let settings = context.settings;
+
+        // Create random f32 between 0.0 and 1.0
+        let random_num = if context.curve.extreme_heights {
+            (context.rng.next_u32() as f32 / u32::MAX as f32).round()
+        } else {
+            context.rng.next_u32() as f32 / u32::MAX as f32
+        };
+
+        // Only roll for a behaviour or pickup when it can happen, so curves without them keep
+        // their layouts
+        let roll = |rng: &mut WyRand, chance: f32| {
+            !settings.passable_only
+                && chance > 0.0
+                && (rng.next_u32() as f32 / u32::MAX as f32) < chance
+        };
+
+        let behaviour = if roll(context.rng, settings.pipe_behaviour_chance) {
+            let index = context.rng.next_u32() as usize % PipeBehaviour::MOVING.len();
+            PipeBehaviour::MOVING[index]
+        } else {
+            PipeBehaviour::Static
+        };
+
+        let pickup = roll(context.rng, settings.pickup_chance).then(|| {
+            let index = context.rng.next_u32() as usize % PickupKind::ALL.len();
+            PickupKind::ALL[index]
+        });
+
+        Obstacle {
+            height: (random_num * settings.pipe_spread) - 2.5,
+            behaviour,
+            pickup,
+            ..Obstacle::from_settings(settings)
+        }
+    }
+}
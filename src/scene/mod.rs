@@ -1,33 +1,47 @@
-pub(crate) mod pipes;
+pub mod course;
+pub mod difficulty;
+pub mod director;
+pub mod generator;
+pub mod pickups;
+pub mod pipes;
+pub mod seed;
+pub mod solver;
 
 use avian3d::math::PI;
+use avian3d::prelude::*;
 use bevy::light::{CascadeShadowConfigBuilder, DirectionalLightShadowMap, NotShadowCaster};
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
 use bevy_rand::prelude::*;
-use rand_core::RngCore;
 
-use pipes::PipePair;
+use crate::gameplay::{ActivePlayArea, GameState, GameplaySystems, PlayArea};
+use crate::player::{self, PlayerSettings, initial_transform};
+use course::{Course, FinishLine};
+use difficulty::{ActiveDifficultyCurve, DifficultyCurve};
+use generator::{GeneratorContext, Obstacle, ObstacleSource};
+use pickups::Pickup;
+use pipes::{GapSensor, PIPE_WIDTH, PipeBehaviour, PipeMotion, PipePair, UpperPipe};
+use solver::{CoursePipe, FlightModel, LayoutSolver};
 
-pub struct ScenePlugin;
+pub use seed::{RunSeed, SeedMode};
+
+pub struct ScenePlugin {
+    /// Spawn the camera, lights and backdrop and load the pipe models
+    pub rendering: bool,
+    /// Use this curve for every [`difficulty::Difficulty`] instead of loading them, which only
+    /// happens when rendering
+    pub difficulty_curve: Option<DifficultyCurve>,
+}
 
 impl Plugin for ScenePlugin {
     fn build(&self, app: &mut App) {
-        app.register_type::<PipePair>()
-            .register_type::<SceneSettings>()
-            .insert_resource(SceneSettings {
-                pipe_gap_x: 7.0,
-                pipe_gap_y: 3.1,
-                pipe_spread: 4.0,
-                pipe_speed: 0.0,
-            })
-            .insert_resource(GlobalAmbientLight {
+        if self.rendering {
+            app.insert_resource(GlobalAmbientLight {
                 color: Color::WHITE,
                 brightness: 500.0,
                 ..default()
             })
             .insert_resource(DirectionalLightShadowMap { size: 4096 })
-            .add_plugins(EntropyPlugin::<WyRand>::default())
             .init_state::<AssetState>()
             .add_loading_state(
                 LoadingState::new(AssetState::Loading)
@@ -35,8 +49,47 @@ impl Plugin for ScenePlugin {
                     .load_collection::<SceneAssets>(),
             )
             .add_systems(Startup, setup)
-            .add_systems(OnEnter(AssetState::Loaded), spawn_level)
-            .add_systems(Update, (recycle_pipes, move_pipes));
+            .add_systems(
+                Update,
+                pipes::attach_pipe_scenes.run_if(in_state(AssetState::Loaded)),
+            );
+        }
+
+        app.register_type::<PipePair>()
+            .register_type::<UpperPipe>()
+            .register_type::<GapSensor>()
+            .register_type::<PipeMotion>()
+            .register_type::<SceneSettings>()
+            .init_resource::<SceneSettings>()
+            .init_resource::<ObstacleSource>()
+            .add_plugins(EntropyPlugin::<WyRand>::default())
+            .add_plugins(seed::SeedPlugin)
+            .add_plugins(course::CoursePlugin {
+                rendering: self.rendering,
+            })
+            .add_plugins(pickups::PickupPlugin {
+                rendering: self.rendering,
+            })
+            .add_plugins(difficulty::DifficultyPlugin {
+                curve: self.difficulty_curve.clone(),
+                load_assets: self.rendering,
+            })
+            .add_systems(
+                OnEnter(GameState::Playing),
+                start_passable_layout.run_if(|settings: Res<SceneSettings>| settings.passable_only),
+            )
+            .add_systems(OnExit(GameState::Playing), stop_passable_layout)
+            .add_systems(
+                FixedUpdate,
+                (
+                    move_pipes,
+                    recycle_pipes.run_if(not(resource_exists::<Course>)),
+                    pipes::animate_pipes,
+                    pipes::fit_gap_sensors,
+                )
+                    .chain()
+                    .in_set(GameplaySystems::Movement),
+            );
     }
 }
 
@@ -48,18 +101,42 @@ enum AssetState {
 }
 
 #[derive(AssetCollection, Resource)]
-struct SceneAssets {
+pub(crate) struct SceneAssets {
     #[asset(path = "objects/pipe.glb#Scene0")]
     pipe: Handle<Scene>,
 }
 
-#[derive(Reflect, Resource, Default)]
+/// The current pipe settings. During a run the pipe values follow the [`DifficultyCurve`] of the
+/// chosen [`difficulty::Difficulty`].
+#[derive(Reflect, Resource, Clone)]
 #[reflect(Resource)]
 pub struct SceneSettings {
-    pipe_gap_x: f32,
-    pipe_gap_y: f32,
-    pipe_spread: f32,
+    pub pipe_gap_x: f32,
+    pub pipe_gap_y: f32,
+    pub pipe_spread: f32,
     pub pipe_speed: f32,
+    /// Chance for a recycled pipe pair to get one of the moving [`PipeBehaviour`]s
+    pub pipe_behaviour_chance: f32,
+    /// Chance for a recycled pipe pair to carry a [`pickups::Pickup`] in its gap
+    pub pickup_chance: f32,
+    /// Only generate pipe heights that the [`solver`] can prove to be passable. The solver only
+    /// knows about static pipes at the speed of the curve, so no pipes are given a moving
+    /// [`PipeBehaviour`] or a [`pickups::Pickup`] either.
+    pub passable_only: bool,
+}
+
+impl Default for SceneSettings {
+    fn default() -> Self {
+        Self {
+            pipe_gap_x: 7.0,
+            pipe_gap_y: 3.1,
+            pipe_spread: 4.0,
+            pipe_speed: 0.0,
+            pipe_behaviour_chance: 0.0,
+            pickup_chance: 0.0,
+            passable_only: false,
+        }
+    }
 }
 
 fn setup(
@@ -110,17 +187,33 @@ fn setup(
     ));
 }
 
-pub fn spawn_level(mut commands: Commands, scene_settings: Res<SceneSettings>) {
-    for i in 0..5 {
+pub fn spawn_level(
+    mut commands: Commands,
+    scene_settings: Res<SceneSettings>,
+    mut obstacles: ResMut<ObstacleSource>,
+    course: Option<Res<Course>>,
+) {
+    if let Some(course) = course {
+        course::spawn_course(&mut commands, &course);
+        return;
+    }
+
+    let mut position_x = 0.0;
+    for (i, obstacle) in obstacles.start(&scene_settings).into_iter().enumerate() {
+        position_x += obstacle.spacing;
         commands.queue(pipes::SpawnPipePair {
-            position_x: (i + 1) as f32 * scene_settings.pipe_gap_x,
+            position_x,
+            height: obstacle.height,
+            gap_y: obstacle.gap_y,
             rotation: i as f32 * PI * 0.5,
+            behaviour: obstacle.behaviour,
+            pickup: obstacle.pickup,
         });
     }
 }
 
-fn move_pipes(
-    mut pipe_query: Query<&mut Transform, With<PipePair>>,
+pub(crate) fn move_pipes(
+    mut pipe_query: Query<&mut Transform, Or<(With<PipePair>, With<FinishLine>)>>,
     time: Res<Time>,
     scene_settings: Res<SceneSettings>,
 ) {
@@ -129,27 +222,210 @@ fn move_pipes(
     }
 }
 
-// Respawn the pipes if they have gone off the screen past the player
+// Respawn the pipes if they have gone off the screen past the player, as the next obstacle of the
+// generator
 fn recycle_pipes(
-    mut pipe_query: Query<&mut Transform, With<PipePair>>,
+    mut commands: Commands,
+    mut pipe_query: Query<(Entity, &mut Transform, &mut PipeMotion, &Children)>,
+    pickup_query: Query<(), With<Pickup>>,
     scene_settings: Res<SceneSettings>,
     mut rng_resource: Single<&mut WyRand, With<GlobalRng>>,
+    mut passable_layout: Option<ResMut<PassableLayout>>,
+    mut obstacles: ResMut<ObstacleSource>,
+    time: Res<Time>,
+    curve: ActiveDifficultyCurve,
 ) {
-    let num_pipes = pipe_query.iter().len() as f32;
-    let pipe_gap_x = scene_settings.pipe_gap_x;
-    let out_of_view_bound = -2.0 * pipe_gap_x;
+    let curve = curve.get();
+    let out_of_view_bound = -2.0 * scene_settings.pipe_gap_x;
+    let mut furthest_x = pipe_query
+        .iter()
+        .map(|(_, pipe_set, _, _)| pipe_set.translation.x)
+        .fold(f32::MIN, f32::max);
 
-    for mut pipe_set in pipe_query.iter_mut() {
+    if let Some(layout) = &mut passable_layout {
+        layout.traveled += scene_settings.pipe_speed * time.delta_secs();
+    }
+
+    for (pipe_pair, mut pipe_set, mut motion, children) in pipe_query.iter_mut() {
         if pipe_set.translation.x < out_of_view_bound {
-            // Create random f32 between 0.0 and 1.0
-            let random_num = if cfg!(feature = "max_difficulty") {
-                (rng_resource.next_u32() as f32 / u32::MAX as f32).round()
-            } else {
-                rng_resource.next_u32() as f32 / u32::MAX as f32
+            let mut context = GeneratorContext {
+                rng: &mut rng_resource,
+                settings: &scene_settings,
+                curve: &curve,
+            };
+            let mut next_obstacle = || obstacles.next(&mut context);
+
+            let obstacle = match &mut passable_layout {
+                Some(layout) => layout.next_obstacle(furthest_x, next_obstacle),
+                None => next_obstacle(),
             };
 
-            pipe_set.translation.x = pipe_gap_x * (num_pipes - 2.0);
-            pipe_set.translation.y = (random_num * scene_settings.pipe_spread) - 2.5;
+            pipe_set.translation.x = furthest_x + obstacle.spacing;
+            furthest_x = pipe_set.translation.x;
+
+            *motion = PipeMotion {
+                behaviour: obstacle.behaviour,
+                rest_y: obstacle.height,
+                rest_gap_y: obstacle.gap_y,
+                elapsed: 0.0,
+            };
+
+            // A pickup that wasn't collected doesn't come around again
+            for child in children
+                .iter()
+                .filter(|child| pickup_query.contains(*child))
+            {
+                commands.entity(child).despawn();
+            }
+            if let Some(kind) = obstacle.pickup {
+                commands
+                    .entity(pipe_pair)
+                    .with_child(pickups::pickup_bundle(kind, obstacle.gap_y));
+            }
+        }
+    }
+}
+
+/// Checks every generated obstacle while [`SceneSettings::passable_only`] is on
+#[derive(Resource)]
+struct PassableLayout {
+    solver: LayoutSolver,
+    /// Distance the pipes have moved since the start of the run
+    traveled: f32,
+    last_height: f32,
+}
+
+impl PassableLayout {
+    const MAX_ATTEMPTS: usize = 20;
+
+    fn next_obstacle(
+        &mut self,
+        furthest_x: f32,
+        mut next_obstacle: impl FnMut() -> Obstacle,
+    ) -> Obstacle {
+        let course_pipe = |obstacle: &Obstacle| CoursePipe {
+            x: furthest_x + obstacle.spacing + self.traveled,
+            y: obstacle.height,
+            gap_y: obstacle.gap_y,
+        };
+        // The solver only knows about static pipes, moving at the speed of the curve
+        let mut next_obstacle = || Obstacle {
+            behaviour: PipeBehaviour::Static,
+            pickup: None,
+            ..next_obstacle()
+        };
+
+        // Every attempt starts from the bird approaching the new pipe, so the ticks before it are
+        // only simulated once. An attempt then costs the few ticks it takes to pass the pipe.
+        let mut approach = self.solver.clone();
+        let mut try_pipe = |solver: &mut LayoutSolver, pipe: CoursePipe| {
+            if !approach.can_push(pipe.x) {
+                approach = solver.clone();
+            }
+
+            let passable = approach.advance_to(pipe.x) && approach.push_pipe(pipe);
+            if passable {
+                *solver = approach.clone();
+            }
+            passable
+        };
+
+        let mut rejected = None;
+        for _ in 0..Self::MAX_ATTEMPTS {
+            let obstacle = next_obstacle();
+            if try_pipe(&mut self.solver, course_pipe(&obstacle)) {
+                self.last_height = obstacle.height;
+                return obstacle;
+            }
+            rejected = Some(obstacle);
+        }
+
+        // Staying at the same height is passable in practice, the solver just couldn't prove it
+        let obstacle = Obstacle {
+            height: self.last_height,
+            ..rejected.unwrap_or_else(next_obstacle)
+        };
+        let pipe = course_pipe(&obstacle);
+        if !try_pipe(&mut self.solver, pipe) {
+            warn!("Could not generate a passable pipe at x = {}", pipe.x);
         }
+
+        obstacle
     }
 }
+
+// The bird as the player is spawned at the start of a run
+fn flight_model(
+    player_settings: &PlayerSettings,
+    gravity: &Gravity,
+    substeps: &SubstepCount,
+    timestep: f32,
+    play_area: &PlayArea,
+    difficulty: DifficultyCurve,
+) -> FlightModel {
+    let bird_aabb =
+        player::collider().aabb(Vec3::ZERO, initial_transform(player_settings).rotation);
+
+    FlightModel {
+        jump_velocity: player_settings.jump_velocity,
+        gravity: gravity.0.y * player::GRAVITY_SCALE,
+        timestep,
+        substeps: substeps.0,
+        start_y: player_settings.initial_position.y,
+        bird_half_width: (bird_aabb.max.x - bird_aabb.min.x) / 2.0,
+        bird_half_height: (bird_aabb.max.y - bird_aabb.min.y) / 2.0,
+        pipe_half_width: PIPE_WIDTH / 2.0,
+        margin: 0.05,
+        bottom: play_area.bottom,
+        top: play_area.top,
+        difficulty,
+    }
+}
+
+fn start_passable_layout(
+    mut commands: Commands,
+    player_settings: Res<PlayerSettings>,
+    play_area: ActivePlayArea,
+    pipe_query: Query<(&Transform, &PipePair)>,
+    gravity: Res<Gravity>,
+    substeps: Res<SubstepCount>,
+    time: Res<Time<Fixed>>,
+    curve: ActiveDifficultyCurve,
+) {
+    let model = flight_model(
+        &player_settings,
+        &gravity,
+        &substeps,
+        time.timestep().as_secs_f32(),
+        play_area.get(),
+        curve.get(),
+    );
+
+    // The pipes of a new level haven't moved yet
+    let mut pipes: Vec<CoursePipe> = pipe_query
+        .iter()
+        .map(|(transform, pipe)| CoursePipe {
+            x: transform.translation.x,
+            y: transform.translation.y,
+            gap_y: pipe.gap_y,
+        })
+        .collect();
+    pipes.sort_by(|a, b| a.x.total_cmp(&b.x));
+
+    let mut solver = LayoutSolver::new(model);
+    for pipe in &pipes {
+        if !solver.push_pipe(*pipe) {
+            warn!("The starting pipes are not passable");
+        }
+    }
+
+    commands.insert_resource(PassableLayout {
+        solver,
+        traveled: 0.0,
+        last_height: pipes.last().map_or(0.0, |pipe| pipe.y),
+    });
+}
+
+fn stop_passable_layout(mut commands: Commands) {
+    commands.remove_resource::<PassableLayout>();
+}
@@ -0,0 +1,208 @@
+//! Power-ups floating in the middle of pipe gaps.
+//!
+//! The [`ObstacleGenerator`](crate::scene::generator::ObstacleGenerator) decides which pipe pairs
+//! carry a [`Pickup`]. Pickups are sensors on their own [`GameLayer`], so flying through one
+//! doesn't count as a crash. It is collected instead and its effect is added to the
+//! [`ActiveEffects`] of the run.
+
+use avian3d::prelude::*;
+use bevy::color::palettes::css::{DEEP_SKY_BLUE, GOLD, MEDIUM_PURPLE};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::gameplay::{GameLayer, GameState, GameplaySystems, scoring};
+use crate::player::Player;
+use crate::scene::SceneSettings;
+use crate::scene::difficulty::apply_difficulty;
+use crate::scene::move_pipes;
+use crate::scene::pipes::{PipePair, animate_pipes};
+
+/// How much slower the pipes move during [`PickupKind::SlowMotion`]
+pub const SLOW_MOTION_SCALE: f32 = 0.6;
+pub const SLOW_MOTION_SECONDS: f32 = 3.0;
+pub const DOUBLE_SCORE_SECONDS: f32 = 8.0;
+/// After the shield breaks the player is [`Invulnerable`](crate::player::health::Invulnerable)
+/// for this long, so the bird can get clear of the pipe
+pub const SHIELD_GRACE_SECONDS: f32 = 1.0;
+
+const PICKUP_RADIUS: f32 = 0.4;
+
+#[derive(Reflect, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PickupKind {
+    /// Survive the next crash into a pipe
+    Shield,
+    /// The pipes move slower for a while
+    SlowMotion,
+    /// Every pipe is worth a bonus point for a while
+    DoubleScore,
+}
+
+impl PickupKind {
+    pub const ALL: [Self; 3] = [Self::Shield, Self::SlowMotion, Self::DoubleScore];
+
+    fn color(self) -> Color {
+        match self {
+            PickupKind::Shield => DEEP_SKY_BLUE.into(),
+            PickupKind::SlowMotion => MEDIUM_PURPLE.into(),
+            PickupKind::DoubleScore => GOLD.into(),
+        }
+    }
+}
+
+/// A power-up in the gap of the [`PipePair`] it is a child of
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub struct Pickup(pub PickupKind);
+
+pub(crate) fn pickup_bundle(kind: PickupKind, gap_y: f32) -> impl Bundle {
+    (
+        Name::from("Pickup"),
+        Pickup(kind),
+        RigidBody::Kinematic,
+        Collider::sphere(PICKUP_RADIUS),
+        Sensor,
+        GameLayer::player_sensor(GameLayer::Pickup),
+        Transform::from_xyz(0.0, gap_y / 2.0, 0.0),
+    )
+}
+
+/// The effects of the pickups collected during the current run
+#[derive(Resource, Reflect, Default, Debug, Clone, PartialEq)]
+#[reflect(Resource)]
+pub struct ActiveEffects {
+    pub shield: bool,
+    /// Seconds left of [`PickupKind::SlowMotion`]
+    pub slow_motion: f32,
+    /// Seconds left of [`PickupKind::DoubleScore`]
+    pub double_score: f32,
+}
+
+impl ActiveEffects {
+    fn add(&mut self, kind: PickupKind) {
+        match kind {
+            PickupKind::Shield => self.shield = true,
+            PickupKind::SlowMotion => self.slow_motion = SLOW_MOTION_SECONDS,
+            PickupKind::DoubleScore => self.double_score = DOUBLE_SCORE_SECONDS,
+        }
+    }
+
+    /// Points for passing a pipe, all but one of them are bonus points
+    pub fn score_multiplier(&self) -> u32 {
+        if self.double_score > 0.0 { 2 } else { 1 }
+    }
+
+    /// Whether the player survives crashing into a pipe. Breaks the shield if there is one.
+    pub fn absorb_crash(&mut self) -> bool {
+        std::mem::take(&mut self.shield)
+    }
+}
+
+/// The player flew through a [`Pickup`]
+#[derive(Message, Debug, Clone, Copy, PartialEq)]
+pub struct PickupCollected(pub PickupKind);
+
+pub(super) struct PickupPlugin {
+    /// Give the pickups a mesh
+    pub rendering: bool,
+}
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Pickup>()
+            .register_type::<ActiveEffects>()
+            .init_resource::<ActiveEffects>()
+            .add_message::<PickupCollected>()
+            .add_systems(OnEnter(GameState::Ready), reset_effects)
+            .add_systems(
+                FixedUpdate,
+                (
+                    wear_off_effects
+                        .in_set(GameplaySystems::Tick)
+                        .run_if(in_state(GameState::Playing)),
+                    slow_down_pipes
+                        .in_set(GameplaySystems::Movement)
+                        .after(apply_difficulty)
+                        .before(move_pipes)
+                        .run_if(in_state(GameState::Playing)),
+                    center_pickups
+                        .in_set(GameplaySystems::Movement)
+                        .after(animate_pipes),
+                    collect_pickups
+                        .in_set(GameplaySystems::Rules)
+                        .before(scoring)
+                        .run_if(in_state(GameState::Playing)),
+                ),
+            );
+
+        if self.rendering {
+            app.add_systems(Update, attach_pickup_meshes);
+        }
+    }
+}
+
+fn reset_effects(mut effects: ResMut<ActiveEffects>) {
+    *effects = ActiveEffects::default();
+}
+
+fn wear_off_effects(mut effects: ResMut<ActiveEffects>, time: Res<Time>) {
+    let delta = time.delta_secs();
+
+    effects.slow_motion = (effects.slow_motion - delta).max(0.0);
+    effects.double_score = (effects.double_score - delta).max(0.0);
+}
+
+// The difficulty sets the pipe speed every timestep, so this only lasts while the effect does
+fn slow_down_pipes(mut scene_settings: ResMut<SceneSettings>, effects: Res<ActiveEffects>) {
+    if effects.slow_motion > 0.0 {
+        scene_settings.pipe_speed *= SLOW_MOTION_SCALE;
+    }
+}
+
+// Keep the pickups in the middle of gaps that open and close
+fn center_pickups(
+    mut pickup_query: Query<(&mut Transform, &ChildOf), With<Pickup>>,
+    pair_query: Query<&PipePair>,
+) {
+    for (mut transform, child_of) in &mut pickup_query {
+        if let Ok(pipe_pair) = pair_query.get(child_of.parent()) {
+            transform.translation.y = pipe_pair.gap_y / 2.0;
+        }
+    }
+}
+
+fn collect_pickups(
+    mut commands: Commands,
+    collisions: Collisions,
+    pickup_query: Query<&Pickup>,
+    player: Single<Entity, With<Player>>,
+    mut effects: ResMut<ActiveEffects>,
+    mut pickup_collected: MessageWriter<PickupCollected>,
+) {
+    for contact_pair in collisions.collisions_with(*player) {
+        for collider in [contact_pair.collider1, contact_pair.collider2] {
+            if let Ok(Pickup(kind)) = pickup_query.get(collider) {
+                effects.add(*kind);
+                pickup_collected.write(PickupCollected(*kind));
+                commands.entity(collider).despawn();
+            }
+        }
+    }
+}
+
+fn attach_pickup_meshes(
+    mut commands: Commands,
+    pickup_query: Query<(Entity, &Pickup), Without<Mesh3d>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, Pickup(kind)) in &pickup_query {
+        commands.entity(entity).insert((
+            Mesh3d(meshes.add(Sphere::new(PICKUP_RADIUS))),
+            MeshMaterial3d(materials.add(StandardMaterial {
+                base_color: kind.color(),
+                emissive: kind.color().to_linear() * 2.0,
+                ..default()
+            })),
+        ));
+    }
+}
@@ -1,59 +1,290 @@
-use crate::scene::{SceneAssets, SceneSettings};
+use std::f32::consts::TAU;
+
+use crate::gameplay::{GameLayer, GameState};
+use crate::scene::SceneAssets;
+use crate::scene::pickups::{PickupKind, pickup_bundle};
 use avian3d::math::PI;
 use avian3d::prelude::*;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Component, Reflect, Default, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct PipePair {
+    /// Height of the gap between the lower and the upper pipe, at the moment
+    pub gap_y: f32,
+}
 
+/// A single upper or lower pipe of a [`PipePair`]
 #[derive(Component, Reflect, Default, Debug)]
 #[reflect(Component)]
-pub struct PipePair;
+pub struct Pipe;
+
+/// The upper [`Pipe`] of a [`PipePair`], placed at the top of the gap
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct UpperPipe;
+
+/// Fills the gap of a [`PipePair`]. The player scores by flying through it.
+///
+/// Keeps track of how the player flies through, to tell a perfect pass or a near miss from a plain
+/// one when the player comes out.
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub struct GapSensor {
+    /// Furthest the middle of the bird got from the middle of the gap
+    pub max_offset: f32,
+    /// Closest the bird got to the lower or upper pipe, negative if it touched one.
+    /// Infinite until the player flies in.
+    pub min_clearance: f32,
+}
+
+impl Default for GapSensor {
+    fn default() -> Self {
+        Self {
+            max_offset: 0.0,
+            min_clearance: f32::INFINITY,
+        }
+    }
+}
+
+/// How a [`PipePair`] moves, apart from scrolling towards the player
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum PipeBehaviour {
+    #[default]
+    Static,
+    /// The gap moves up and down by `amplitude`
+    Oscillate { amplitude: f32, period: f32 },
+    /// The pipes move towards each other until the gap is `amount` smaller, and back
+    Close { amount: f32, period: f32 },
+    /// The pair rocks back and forth around the middle of the gap, by up to `max_angle` radians
+    Rotate { max_angle: f32, period: f32 },
+}
+
+impl PipeBehaviour {
+    pub fn is_static(&self) -> bool {
+        *self == Self::Static
+    }
+
+    /// Every behaviour the generator picks from, with their default strength
+    pub const MOVING: [Self; 3] = [
+        Self::Oscillate {
+            amplitude: 1.0,
+            period: 2.0,
+        },
+        Self::Close {
+            amount: 0.6,
+            period: 1.5,
+        },
+        Self::Rotate {
+            max_angle: 0.25,
+            period: 2.5,
+        },
+    ];
+
+    fn pose(self, rest_gap_y: f32, elapsed: f32) -> PipePose {
+        let wave = |period: f32| (elapsed / period * TAU).sin();
+        let rest = PipePose {
+            offset_y: 0.0,
+            gap_y: rest_gap_y,
+            tilt: 0.0,
+        };
+
+        match self {
+            Self::Static => rest,
+            Self::Oscillate { amplitude, period } => PipePose {
+                offset_y: amplitude * wave(period),
+                ..rest
+            },
+            Self::Close { amount, period } => {
+                // Starts out open
+                let closed = amount * (1.0 - (elapsed / period * TAU).cos()) / 2.0;
+                PipePose {
+                    offset_y: closed / 2.0,
+                    gap_y: rest_gap_y - closed,
+                    ..rest
+                }
+            }
+            Self::Rotate { max_angle, period } => PipePose {
+                tilt: max_angle * wave(period),
+                ..rest
+            },
+        }
+    }
+}
+
+struct PipePose {
+    /// Height of the bottom of the gap above its resting height
+    offset_y: f32,
+    gap_y: f32,
+    /// Rotation around the middle of the gap, in radians
+    tilt: f32,
+}
+
+/// Where a [`PipePair`] is when its [`PipeBehaviour`] leaves it at rest. The pair's transform and
+/// [`PipePair::gap_y`] are derived from this every fixed timestep.
+#[derive(Component, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct PipeMotion {
+    pub behaviour: PipeBehaviour,
+    /// Height of the bottom of the gap
+    pub rest_y: f32,
+    pub rest_gap_y: f32,
+    /// Seconds of the run since the behaviour started
+    pub elapsed: f32,
+}
+
+/// Width and depth of a pipe's collider
+pub(crate) const PIPE_WIDTH: f32 = 1.9;
 
 pub struct SpawnPipePair {
     pub position_x: f32,
+    /// Height of the bottom of the gap
+    pub height: f32,
+    pub gap_y: f32,
     pub rotation: f32,
+    pub behaviour: PipeBehaviour,
+    pub pickup: Option<PickupKind>,
 }
 
 impl Command for SpawnPipePair {
     fn apply(self, world: &mut World) {
-        let assets = world.get_resource::<SceneAssets>();
-        let scene_settings = world.get_resource::<SceneSettings>().unwrap();
-
-        if let Some(assets) = assets {
-            let collider_length = 10.0;
-
-            let pipe_handle = assets.pipe.clone();
-
-            let transform_lower = Transform::from_xyz(0.0, 0.0, 0.0);
-            let mut transform_upper = Transform::from_xyz(0.0, scene_settings.pipe_gap_y, 0.0);
-            transform_upper.rotate_local_z(PI);
-
-            let mut parent_transform = Transform::from_xyz(self.position_x, 0.0, 0.0);
-            parent_transform.rotate_local_y(self.rotation);
-
-            let parent_components = (
-                Name::from("PipePair"),
-                PipePair,
-                Visibility::default(),
-                parent_transform,
-            );
-
-            world.spawn(parent_components).with_children(|parent| {
-                let transforms = [transform_lower, transform_upper];
-                for transform in transforms {
-                    let pipe_components = (
-                        Name::from("Pipe"),
-                        RigidBody::Kinematic,
-                        SceneRoot(pipe_handle.clone()),
-                        transform,
-                    );
-
-                    let collider_components = (
-                        Collider::cuboid(1.9, collider_length, 1.9),
-                        Transform::from_xyz(0.0, -collider_length / 2.0, 0.0),
-                    );
-
-                    parent.spawn((pipe_components, children![collider_components]));
+        self.spawn(world);
+    }
+}
+
+impl SpawnPipePair {
+    /// Spawns the pair right away, returns the [`PipePair`] entity
+    pub fn spawn(self, world: &mut World) -> Entity {
+        let gap_y = self.gap_y;
+
+        let transform_lower = Transform::from_xyz(0.0, 0.0, 0.0);
+        let mut transform_upper = Transform::from_xyz(0.0, gap_y, 0.0);
+        transform_upper.rotate_local_z(PI);
+
+        let mut parent_transform = Transform::from_xyz(self.position_x, self.height, 0.0);
+        parent_transform.rotate_local_y(self.rotation);
+
+        let parent_components = (
+            Name::from("PipePair"),
+            PipePair { gap_y },
+            PipeMotion {
+                behaviour: self.behaviour,
+                rest_y: self.height,
+                rest_gap_y: gap_y,
+                elapsed: 0.0,
+            },
+            Visibility::default(),
+            parent_transform,
+        );
+
+        world
+            .spawn(parent_components)
+            .with_children(|parent| {
+                parent.spawn(pipe_bundle(transform_lower));
+                parent.spawn((pipe_bundle(transform_upper), UpperPipe));
+                parent.spawn(gap_sensor_bundle(gap_y));
+                if let Some(kind) = self.pickup {
+                    parent.spawn(pickup_bundle(kind, gap_y));
                 }
+            })
+            .id()
+    }
+}
+
+fn pipe_bundle(transform: Transform) -> impl Bundle {
+    let collider_length = 10.0;
+
+    let pipe_components = (Name::from("Pipe"), Pipe, RigidBody::Kinematic, transform);
+
+    let collider_components = (
+        Collider::cuboid(PIPE_WIDTH, collider_length, PIPE_WIDTH),
+        GameLayer::obstacle(),
+        Transform::from_xyz(0.0, -collider_length / 2.0, 0.0),
+    );
+
+    (pipe_components, children![collider_components])
+}
+
+fn gap_sensor_bundle(gap_y: f32) -> impl Bundle {
+    (
+        Name::from("GapSensor"),
+        GapSensor::default(),
+        RigidBody::Kinematic,
+        Collider::cuboid(PIPE_WIDTH, gap_y, PIPE_WIDTH),
+        Sensor,
+        GameLayer::player_sensor(GameLayer::Trigger),
+        // Scoring reads when the player leaves the sensor
+        CollisionEventsEnabled,
+        Transform::from_xyz(0.0, gap_y / 2.0, 0.0),
+    )
+}
+
+// The pipes are kinematic bodies, so moving their transforms also moves their colliders.
+// They only move during a run, so a run plays out the same no matter how long it took to start.
+pub(crate) fn animate_pipes(
+    mut pair_query: Query<(&mut Transform, &mut PipePair, &mut PipeMotion, &Children)>,
+    mut pipe_query: Query<(&mut Transform, Has<UpperPipe>), (With<Pipe>, Without<PipePair>)>,
+    state: Res<State<GameState>>,
+    time: Res<Time>,
+) {
+    for (mut pair_transform, mut pipe_pair, mut motion, children) in pair_query.iter_mut() {
+        if *state == GameState::Playing {
+            motion.elapsed += time.delta_secs();
+        }
+        let pose = motion.behaviour.pose(motion.rest_gap_y, motion.elapsed);
+
+        pair_transform.translation.y = motion.rest_y + pose.offset_y;
+        pipe_pair.set_if_neq(PipePair { gap_y: pose.gap_y });
+
+        // The pair itself is rotated around y, so tilt around the axis that is z in world space
+        let pivot = Vec3::Y * pose.gap_y / 2.0;
+        let tilt = Quat::from_axis_angle(pair_transform.rotation.inverse() * Vec3::Z, pose.tilt);
+
+        let mut pipes = pipe_query.iter_many_mut(children);
+        while let Some((mut pipe_transform, upper)) = pipes.fetch_next() {
+            let (rest_translation, rest_rotation) = if upper {
+                (Vec3::Y * pose.gap_y, Quat::from_rotation_z(PI))
+            } else {
+                (Vec3::ZERO, Quat::IDENTITY)
+            };
+
+            pipe_transform.set_if_neq(Transform {
+                translation: pivot + tilt * (rest_translation - pivot),
+                rotation: tilt * rest_rotation,
+                ..*pipe_transform
             });
         }
     }
 }
+
+// Keep the sensors the size of gaps that open and close. They don't tilt with the pipes, a bird
+// passing the middle of a tilted gap still touches them.
+pub(crate) fn fit_gap_sensors(
+    mut sensor_query: Query<(&mut Transform, &mut Collider, &ChildOf), With<GapSensor>>,
+    pair_query: Query<Ref<PipePair>>,
+) {
+    for (mut transform, mut collider, child_of) in &mut sensor_query {
+        let Ok(pipe_pair) = pair_query.get(child_of.parent()) else {
+            continue;
+        };
+        if !pipe_pair.is_changed() {
+            continue;
+        }
+
+        transform.translation.y = pipe_pair.gap_y / 2.0;
+        *collider = Collider::cuboid(PIPE_WIDTH, pipe_pair.gap_y, PIPE_WIDTH);
+    }
+}
+
+// The pipe models are added separately so the pipes themselves don't have to wait for
+// the assets to load, and so they can be left out entirely when running headless.
+pub(crate) fn attach_pipe_scenes(
+    mut commands: Commands,
+    pipe_query: Query<Entity, (With<Pipe>, Without<SceneRoot>)>,
+    assets: Res<SceneAssets>,
+) {
+    for pipe in &pipe_query {
+        commands.entity(pipe).insert(SceneRoot(assets.pipe.clone()));
+    }
+}
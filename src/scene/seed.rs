@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::gameplay::GameState;
+
+/// How the [`RunSeed`] of each run is chosen
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeedMode {
+    /// A different pipe layout every run
+    #[default]
+    Random,
+    /// The same pipe layout every run
+    Fixed(u64),
+    /// The same pipe layout for everyone on the same (UTC) day, with its own high score
+    Daily,
+}
+
+impl SeedMode {
+    /// The seed of today's daily challenge
+    pub fn daily_seed() -> u64 {
+        // SplitMix64, so consecutive days get unrelated layouts
+        let mut z = current_day().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// Days since the Unix epoch, in UTC
+pub fn current_day() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs() / 86_400)
+        .unwrap_or_default()
+}
+
+/// Seed of the pipe layout of a run.
+///
+/// A new one is picked according to the [`SeedMode`] when entering [`GameState::Ready`],
+/// it can be overwritten before the run starts to replay a specific layout.
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq, Deref)]
+pub struct RunSeed(pub u64);
+
+pub(super) struct SeedPlugin;
+
+impl Plugin for SeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SeedMode>()
+            .init_resource::<RunSeed>()
+            .add_systems(OnEnter(GameState::Ready), pick_run_seed)
+            .add_systems(OnEnter(GameState::Playing), seed_run);
+    }
+}
+
+fn pick_run_seed(
+    mut run_seed: ResMut<RunSeed>,
+    seed_mode: Res<SeedMode>,
+    mut rng_resource: Single<&mut WyRand, With<GlobalRng>>,
+) {
+    run_seed.0 = match *seed_mode {
+        SeedMode::Random => rng_resource.next_u64(),
+        SeedMode::Fixed(seed) => seed,
+        SeedMode::Daily => SeedMode::daily_seed(),
+    };
+}
+
+fn seed_run(run_seed: Res<RunSeed>, mut global: GlobalRngEntity<WyRand>) {
+    global.rng_commands().reseed(run_seed.to_le_bytes());
+}
@@ -0,0 +1,371 @@
+//! Checks whether a pipe layout can be flown through at all.
+//!
+//! The solver follows every height the bird could be at, tick by tick, using the same integration
+//! as the physics engine. The bird's velocity only depends on the number of ticks since its last
+//! jump, so the reachable heights are kept per number of ticks since the last jump.
+//! A layout is passable as long as some of those heights survive every pipe.
+//!
+//! Heights closer together than [`RESOLUTION`] are merged by keeping only one of them. The solver
+//! can miss very narrow paths because of that, but every path it finds can really be flown.
+
+use crate::scene::difficulty::DifficultyCurve;
+
+/// Reachable heights closer together than this are merged
+pub const RESOLUTION: f32 = 0.05;
+
+/// Everything about the player's physics and the pipes that matters for passing a pipe
+#[derive(Debug, Clone)]
+pub struct FlightModel {
+    pub jump_velocity: f32,
+    /// Gravity acceleration on the bird, including its gravity scale
+    pub gravity: f32,
+    pub timestep: f32,
+    pub substeps: u32,
+    /// Height of the bird when the run starts
+    pub start_y: f32,
+    /// Half the width and height of the bird's bounding box
+    pub bird_half_width: f32,
+    pub bird_half_height: f32,
+    pub pipe_half_width: f32,
+    /// Extra room kept around the bird, the physics engine reports contacts slightly early
+    pub margin: f32,
+    /// The [`PlayArea`](crate::gameplay::PlayArea) the bird has to stay in. Flying out is never
+    /// counted as a way through, even when the play area clamps or wraps the bird.
+    pub bottom: f32,
+    pub top: f32,
+    /// The pipe speed follows this curve
+    pub difficulty: DifficultyCurve,
+}
+
+/// A pipe pair in course coordinates, where the pipes stand still and the bird starts at x = 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoursePipe {
+    pub x: f32,
+    /// Height of the bottom of the gap
+    pub y: f32,
+    /// Distance between the lower and upper pipe
+    pub gap_y: f32,
+}
+
+/// Checks a whole layout. Returns the index of the first pipe that can't be passed.
+pub fn check_layout(model: &FlightModel, pipes: &[CoursePipe]) -> Result<(), usize> {
+    let mut solver = LayoutSolver::new(model.clone());
+
+    for (index, pipe) in pipes.iter().enumerate() {
+        if !solver.push_pipe(*pipe) {
+            return Err(index);
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks a layout one pipe at a time, so it can be used while generating one
+#[derive(Debug, Clone)]
+pub struct LayoutSolver {
+    model: FlightModel,
+    /// Height change during a tick, by number of ticks since the last jump
+    displacements: Vec<f32>,
+    // Velocity at the end of the last tick in `displacements`
+    fall_velocity: f32,
+    /// Fixed timesteps since the start of the run
+    tick: u64,
+    /// Pipes passed so far
+    score: u32,
+    /// Distance the pipes have moved since the start of the run
+    traveled: f32,
+    /// The heights the bird can be at, by number of ticks since the last jump. Sorted.
+    reachable: Vec<Vec<f32>>,
+    /// Pipes that still have to be passed
+    pipes: Vec<CoursePipe>,
+}
+
+impl LayoutSolver {
+    pub fn new(model: FlightModel) -> Self {
+        let mut solver = Self {
+            displacements: Vec::new(),
+            fall_velocity: model.jump_velocity,
+            tick: 0,
+            score: 0,
+            traveled: 0.0,
+            // Starting the run is also a jump, on the first tick
+            reachable: vec![vec![model.start_y]],
+            pipes: Vec::new(),
+            model,
+        };
+
+        solver.reachable[0][0] += solver.displacement(0);
+        solver.move_pipes();
+
+        solver
+    }
+
+    /// Moves on until a pipe at `x` is about to matter. Those ticks are the same for every pipe
+    /// that could go there, so trying several of them only has to simulate them once.
+    /// Returns false if no height is left.
+    pub fn advance_to(&mut self, x: f32) -> bool {
+        while x - self.traveled - self.tick_distance(self.tick + 1) >= self.reach() {
+            if !self.step() {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Whether a pipe at `x` can still be added, it must not have mattered yet
+    pub fn can_push(&self, x: f32) -> bool {
+        x - self.traveled >= self.reach()
+    }
+
+    /// Adds the next pipe and checks that it can be passed. This simulates every tick until the
+    /// bird is past the pipe, see [`LayoutSolver::advance_to`] to only do part of that once.
+    /// A pipe that can't be passed is not added, so another one can be tried instead.
+    pub fn push_pipe(&mut self, pipe: CoursePipe) -> bool {
+        let mut solver = self.clone();
+        solver.pipes.push(pipe);
+
+        while !solver.pipes.is_empty() {
+            if !solver.step() {
+                return false;
+            }
+        }
+
+        *self = solver;
+        true
+    }
+
+    // Advances one tick, returns false if no height is left
+    fn step(&mut self) -> bool {
+        let mut next = vec![Vec::new(); self.reachable.len() + 1];
+        self.displacement(self.reachable.len());
+
+        // Jumping needs the action to be released for a tick first
+        let jump_displacement = self.displacements[0];
+        let mut jumps: Vec<f32> = self
+            .reachable
+            .iter()
+            .skip(1)
+            .flatten()
+            .map(|height| height + jump_displacement)
+            .collect();
+        jumps.sort_unstable_by(f32::total_cmp);
+        next[0] = jumps;
+
+        for (since_jump, heights) in self.reachable.iter().enumerate() {
+            let displacement = self.displacements[since_jump + 1];
+            next[since_jump + 1] = heights.iter().map(|height| height + displacement).collect();
+        }
+
+        self.move_pipes();
+
+        let (min, max) = self.allowed_heights();
+        for heights in &mut next {
+            heights.retain(|height| (min..=max).contains(height));
+            heights.dedup_by(|height, kept| *height - *kept < RESOLUTION);
+        }
+
+        // Falling for long enough always ends out of bounds
+        while next.last().is_some_and(Vec::is_empty) {
+            next.pop();
+        }
+
+        self.reachable = next;
+        !self.reachable.is_empty()
+    }
+
+    fn move_pipes(&mut self) {
+        self.tick += 1;
+        self.traveled += self.tick_distance(self.tick);
+
+        let reach = self.reach();
+        let traveled = self.traveled;
+        // A pipe scores once the bird is out of its gap, which is also when it no longer matters
+        let pipes = self.pipes.len();
+        self.pipes.retain(|pipe| pipe.x - traveled > -reach);
+        self.score += (pipes - self.pipes.len()) as u32;
+    }
+
+    fn allowed_heights(&self) -> (f32, f32) {
+        let model = &self.model;
+        let reach = self.reach();
+        let clearance = model.bird_half_height + model.margin;
+
+        self.pipes
+            .iter()
+            .filter(|pipe| (pipe.x - self.traveled).abs() < reach)
+            .fold((model.bottom, model.top), |(min, max), pipe| {
+                (
+                    min.max(pipe.y + clearance),
+                    max.min(pipe.y + pipe.gap_y - clearance),
+                )
+            })
+    }
+
+    // Distance the pipes move during `tick`
+    fn tick_distance(&self, tick: u64) -> f32 {
+        let model = &self.model;
+        let pipe_speed = model
+            .difficulty
+            .sample_run(tick, model.timestep, self.score)
+            .pipe_speed;
+
+        pipe_speed * model.timestep
+    }
+
+    // How close a pipe can get horizontally before the bird could touch it
+    fn reach(&self) -> f32 {
+        self.model.pipe_half_width + self.model.bird_half_width + self.model.margin
+    }
+
+    fn displacement(&mut self, since_jump: usize) -> f32 {
+        let model = &self.model;
+        let substep = model.timestep / model.substeps as f32;
+
+        while self.displacements.len() <= since_jump {
+            let mut displacement = 0.0;
+            for _ in 0..model.substeps {
+                self.fall_velocity += model.gravity * substep;
+                displacement += self.fall_velocity * substep;
+            }
+            self.displacements.push(displacement);
+        }
+
+        self.displacements[since_jump]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use avian3d::prelude::*;
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::gameplay::PlayArea;
+    use crate::player::PlayerSettings;
+    use crate::scene::PassableLayout;
+    use crate::scene::difficulty::Difficulty;
+    use crate::scene::generator::Obstacle;
+    use crate::scene::pipes::PipeBehaviour;
+
+    // The default player and physics settings
+    fn flight_model() -> FlightModel {
+        crate::scene::flight_model(
+            &PlayerSettings::default(),
+            &Gravity::default(),
+            &SubstepCount::default(),
+            Time::<Fixed>::default().timestep().as_secs_f32(),
+            &PlayArea::default(),
+            DifficultyCurve::preset(Difficulty::Normal),
+        )
+    }
+
+    fn pipe(x: f32, y: f32) -> CoursePipe {
+        CoursePipe { x, y, gap_y: 3.1 }
+    }
+
+    #[test]
+    fn passable_layout() {
+        let pipes = [
+            pipe(7.0, -0.5),
+            pipe(14.0, 1.0),
+            pipe(21.0, -1.5),
+            pipe(28.0, 0.0),
+        ];
+
+        assert_eq!(check_layout(&flight_model(), &pipes), Ok(()));
+    }
+
+    #[test]
+    fn impossible_layout() {
+        // The pipes overlap, but their gaps don't
+        let pipes = [pipe(7.0, -4.0), pipe(8.0, 4.0), pipe(15.0, 0.0)];
+
+        assert_eq!(check_layout(&flight_model(), &pipes), Err(1));
+    }
+
+    #[test]
+    fn gap_too_narrow() {
+        let narrow = CoursePipe {
+            gap_y: 0.3,
+            ..pipe(14.0, 0.0)
+        };
+
+        assert_eq!(
+            check_layout(&flight_model(), &[pipe(7.0, -0.5), narrow]),
+            Err(1)
+        );
+    }
+
+    #[test]
+    fn rejected_pipe_is_not_added() {
+        let mut solver = LayoutSolver::new(flight_model());
+
+        assert!(solver.push_pipe(pipe(7.0, -0.5)));
+        assert!(!solver.push_pipe(pipe(8.0, 6.0)));
+        assert!(solver.push_pipe(pipe(14.0, 0.0)));
+    }
+
+    #[test]
+    fn advancing_gives_the_same_result() {
+        let mut solver = LayoutSolver::new(flight_model());
+        assert!(solver.push_pipe(pipe(7.0, -0.5)));
+
+        for y in [-4.0, -1.0, 0.0, 2.0, 5.0] {
+            let mut advanced = solver.clone();
+            assert!(advanced.advance_to(14.0));
+            assert!(advanced.can_push(14.0));
+
+            assert_eq!(
+                advanced.push_pipe(pipe(14.0, y)),
+                solver.clone().push_pipe(pipe(14.0, y)),
+                "pipe at height {y}"
+            );
+        }
+    }
+
+    // The solver has passed the first pipe at x = 7
+    fn passable_layout_after_one_pipe() -> PassableLayout {
+        let mut solver = LayoutSolver::new(flight_model());
+        assert!(solver.push_pipe(pipe(7.0, -0.5)));
+
+        PassableLayout {
+            solver,
+            traveled: 0.0,
+            last_height: -0.5,
+        }
+    }
+
+    fn obstacle(height: f32) -> Obstacle {
+        Obstacle {
+            spacing: 7.0,
+            height,
+            gap_y: 3.1,
+            behaviour: PipeBehaviour::Static,
+            pickup: None,
+        }
+    }
+
+    #[test]
+    fn passable_only_skips_impossible_obstacles() {
+        let mut layout = passable_layout_after_one_pipe();
+        let mut heights = [8.0, 12.0, 0.5].into_iter();
+
+        let chosen = layout.next_obstacle(7.0, || obstacle(heights.next().unwrap()));
+
+        assert_eq!(chosen.height, 0.5);
+        assert_eq!(layout.last_height, 0.5);
+    }
+
+    #[test]
+    fn passable_only_falls_back_to_the_last_height() {
+        let mut layout = passable_layout_after_one_pipe();
+
+        // Above the play area, never passable
+        let chosen = layout.next_obstacle(7.0, || obstacle(8.0));
+
+        assert_eq!(chosen.height, -0.5);
+        // The fallback was added to the layout, the solver is past it
+        assert!(!layout.solver.can_push(14.0));
+    }
+}
@@ -1,11 +1,34 @@
 use crate::gameplay::{GameState, ScoreInfo};
+use crate::scene::difficulty::Difficulty;
 use bevy::prelude::*;
+use serde::Deserialize;
 use std::io::{Read, Write};
 
 const PATH: &str = "./score.ron";
 
 pub struct SavePlugin;
 
+// Saved before there were difficulty presets, all of those runs were played on Normal
+#[derive(Deserialize)]
+struct LegacyScoreInfo {
+    high_score: u32,
+}
+
+impl From<LegacyScoreInfo> for ScoreInfo {
+    fn from(legacy: LegacyScoreInfo) -> Self {
+        Self {
+            high_scores: [(Difficulty::Normal, legacy.high_score)].into(),
+            ..default()
+        }
+    }
+}
+
+fn parse(data: &str) -> Option<ScoreInfo> {
+    ron::from_str(data)
+        .ok()
+        .or_else(|| ron::from_str::<LegacyScoreInfo>(data).ok().map(Into::into))
+}
+
 impl Plugin for SavePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, load_high_score)
@@ -20,7 +43,7 @@ fn load_high_score(mut score_info: ResMut<ScoreInfo>) {
         file.read_to_string(&mut data_buffer)
             .expect("Could not read ron file");
 
-        if let Ok(data) = ron::from_str(&data_buffer) {
+        if let Some(data) = parse(&data_buffer) {
             *score_info = data;
         }
     } else {
@@ -29,10 +52,40 @@ fn load_high_score(mut score_info: ResMut<ScoreInfo>) {
 }
 
 fn save_high_score(score_info: Res<ScoreInfo>) {
-    let score_copy = *score_info;
-    let data = ron::to_string(&score_copy).unwrap();
+    let data = ron::to_string(&*score_info).unwrap();
 
     // write data to file
     let mut file = std::fs::File::create(PATH).unwrap();
     file.write_all(data.as_bytes()).unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_format() {
+        let score_info = ScoreInfo {
+            current_score: 3,
+            high_scores: [(Difficulty::Hard, 12)].into(),
+            daily_day: 20000,
+            ..default()
+        };
+
+        let parsed = parse(&ron::to_string(&score_info).unwrap()).unwrap();
+        assert_eq!(parsed.high_scores, score_info.high_scores);
+        assert_eq!(parsed.daily_day, 20000);
+    }
+
+    #[test]
+    fn baseline_format_is_migrated_to_normal() {
+        let parsed = parse("(current_score: 4, high_score: 17)").unwrap();
+        assert_eq!(parsed.current_score, 0);
+        assert_eq!(parsed.high_scores, [(Difficulty::Normal, 17)].into());
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert!(parse("(best: 17)").is_none());
+    }
+}
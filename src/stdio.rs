@@ -0,0 +1,174 @@
+//! Lets another program play the game over stdin and stdout, one line of JSON per fixed timestep.
+//!
+//! A [`TickReport`] is written at startup and after every timestep. Before each timestep one line
+//! like `{"action": "jump"}` or `{"action": "idle"}` is read to decide what the bird does.
+//! The game ends when stdin is closed.
+
+use std::io::Write;
+
+use bevy::prelude::*;
+use bevy::state::state::StateTransitionEvent;
+use serde::{Deserialize, Serialize};
+
+use crate::env::{
+    AgentAction, AgentInputPlugin, Observation, PendingAction, apply_action, observe,
+};
+use crate::gameplay::{
+    DeathCause, GameState, JumpedEvent, PassKind, PlayerDied, ScoreInfo, ScoredEvent,
+    SimulationTick,
+};
+use crate::scene::course::CourseCompleted;
+
+/// A line read from stdin
+#[derive(Debug, Deserialize)]
+pub struct ActionMessage {
+    pub action: AgentAction,
+}
+
+/// A line written to stdout
+#[derive(Debug, Serialize)]
+pub struct TickReport {
+    pub tick: u64,
+    pub state: GameState,
+    pub score: u32,
+    pub observation: Observation,
+    /// What happened during the timestep, in order
+    pub events: Vec<ProtocolEvent>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProtocolEvent {
+    Jumped,
+    Scored {
+        score: u32,
+        pass: PassKind,
+        bonus: u32,
+        combo: u32,
+    },
+    StateChanged {
+        from: Option<GameState>,
+        to: Option<GameState>,
+    },
+    Died {
+        cause: DeathCause,
+        score: u32,
+        tick: u64,
+    },
+    CourseCompleted {
+        score: u32,
+        seconds: f32,
+    },
+    /// The line read from stdin was not a valid [`ActionMessage`], the bird stays idle
+    InvalidAction {
+        message: String,
+    },
+}
+
+/// Drives the game from stdin and reports on stdout. Meant for a headless app that advances
+/// one fixed timestep per update.
+pub struct StdioPlugin;
+
+#[derive(Resource, Default)]
+struct TickEvents(Vec<ProtocolEvent>);
+
+impl Plugin for StdioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(AgentInputPlugin)
+            .init_resource::<TickEvents>()
+            .add_systems(PostStartup, write_report)
+            .add_systems(FixedUpdate, read_action.before(apply_action))
+            .add_systems(FixedLast, (collect_events, write_report).chain());
+    }
+}
+
+fn read_action(
+    mut pending_action: ResMut<PendingAction>,
+    mut events: ResMut<TickEvents>,
+    mut app_exit: MessageWriter<AppExit>,
+) {
+    let mut line = String::new();
+
+    pending_action.0 = match std::io::stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => {
+            app_exit.write(AppExit::Success);
+            AgentAction::Idle
+        }
+        Ok(_) => match serde_json::from_str::<ActionMessage>(&line) {
+            Ok(message) => message.action,
+            Err(err) => {
+                events.0.push(ProtocolEvent::InvalidAction {
+                    message: err.to_string(),
+                });
+                AgentAction::Idle
+            }
+        },
+    };
+}
+
+fn collect_events(
+    mut jumped_event: MessageReader<JumpedEvent>,
+    mut scored_event: MessageReader<ScoredEvent>,
+    mut course_completed: MessageReader<CourseCompleted>,
+    mut player_died: MessageReader<PlayerDied>,
+    mut transition_event: MessageReader<StateTransitionEvent<GameState>>,
+    mut events: ResMut<TickEvents>,
+    score_info: Res<ScoreInfo>,
+    tick: Res<SimulationTick>,
+) {
+    for _ in jumped_event.read() {
+        events.0.push(ProtocolEvent::Jumped);
+    }
+
+    for scored in scored_event.read() {
+        events.0.push(ProtocolEvent::Scored {
+            score: score_info.current_score,
+            pass: scored.pass,
+            bonus: scored.bonus,
+            combo: scored.combo,
+        });
+    }
+
+    for completed in course_completed.read() {
+        events.0.push(ProtocolEvent::CourseCompleted {
+            score: completed.score,
+            seconds: completed.seconds,
+        });
+    }
+
+    for died in player_died.read() {
+        events.0.push(ProtocolEvent::Died {
+            cause: died.cause,
+            score: died.score,
+            tick: **tick,
+        });
+    }
+
+    for transition in transition_event.read() {
+        events.0.push(ProtocolEvent::StateChanged {
+            from: transition.exited,
+            to: transition.entered,
+        });
+    }
+}
+
+fn write_report(world: &mut World) {
+    let report = TickReport {
+        tick: **world.resource::<SimulationTick>(),
+        state: *world.resource::<State<GameState>>().get(),
+        score: world.resource::<ScoreInfo>().current_score,
+        observation: observe(world),
+        events: std::mem::take(&mut world.resource_mut::<TickEvents>().0),
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    let written = serde_json::to_writer(&mut stdout, &report)
+        .map_err(std::io::Error::from)
+        .and_then(|()| writeln!(stdout))
+        .and_then(|()| stdout.flush());
+
+    // Nobody is listening anymore
+    if written.is_err() {
+        world.write_message(AppExit::Success);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::player::Player;
+use crate::player::health::Health;
+use crate::scene::pickups::ActiveEffects;
+use bevy::prelude::*;
+
+pub struct EffectsTextPlugin;
+
+impl Plugin for EffectsTextPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup)
+            .add_systems(Update, update_effects);
+    }
+}
+
+#[derive(Component)]
+struct EffectsText;
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        Text::default(),
+        TextFont::from_font_size(40.0),
+        TextColor::WHITE,
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(20.0),
+            top: Val::Px(20.0),
+            ..default()
+        },
+        EffectsText,
+    ));
+}
+
+fn update_effects(
+    mut effects_text: Single<&mut Text, With<EffectsText>>,
+    effects: Res<ActiveEffects>,
+    health: Single<Option<Ref<Health>>, With<Player>>,
+) {
+    let health_changed = health.as_ref().is_some_and(|health| health.is_changed());
+    if !effects.is_changed() && !health_changed {
+        return;
+    }
+
+    let mut lines = Vec::new();
+    if let Some(health) = &*health {
+        lines.push(format!("Health {}/{}", health.current, health.max));
+    }
+    if effects.shield {
+        lines.push("Shield".to_string());
+    }
+    if effects.slow_motion > 0.0 {
+        lines.push(format!("Slow motion {:.1}", effects.slow_motion));
+    }
+    if effects.double_score > 0.0 {
+        lines.push(format!("Double score {:.1}", effects.double_score));
+    }
+
+    effects_text.0 = lines.join("\n");
+}
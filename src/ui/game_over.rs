@@ -0,0 +1,57 @@
+use crate::gameplay::{GameState, PlayerDied};
+use bevy::prelude::*;
+
+pub struct GameOverTextPlugin;
+
+impl Plugin for GameOverTextPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup)
+            .add_systems(Update, show_death)
+            .add_systems(OnEnter(GameState::Ready), hide);
+    }
+}
+
+#[derive(Component)]
+struct GameOverText;
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        Text::default(),
+        TextFont::from_font_size(50.0),
+        TextColor::WHITE,
+        TextLayout::new_with_justify(Justify::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            justify_self: JustifySelf::Center,
+            align_self: AlignSelf::Center,
+            ..default()
+        },
+        Visibility::Hidden,
+        GameOverText,
+    ));
+}
+
+fn show_death(
+    mut player_died: MessageReader<PlayerDied>,
+    game_over_text: Single<(&mut Text, &mut Visibility), With<GameOverText>>,
+) {
+    let Some(died) = player_died.read().last() else {
+        return;
+    };
+    let (mut text, mut visibility) = game_over_text.into_inner();
+
+    // "hit the top pipe at 23" becomes "Hit the top pipe at 23"
+    let cause = died.cause.to_string();
+    let mut chars = cause.chars();
+    let cause: String = chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default();
+
+    text.0 = format!("{cause} at {}", died.total_score);
+    *visibility = Visibility::Inherited;
+}
+
+fn hide(mut visibility: Single<&mut Visibility, With<GameOverText>>) {
+    **visibility = Visibility::Hidden;
+}
@@ -7,6 +7,7 @@ impl Plugin for PromptPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup)
             .add_systems(OnEnter(GameState::Playing), hide)
+            .add_systems(OnEnter(GameState::Editor), hide)
             .add_systems(OnEnter(GameState::Ready), show);
     }
 }
@@ -1,4 +1,7 @@
+mod effects;
+mod game_over;
 mod input_prompts;
+mod pause_menu;
 mod score;
 
 use bevy::prelude::*;
@@ -8,6 +11,9 @@ pub struct UiPlugin;
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(score::ScoreTextPlugin)
-            .add_plugins(input_prompts::PromptPlugin);
+            .add_plugins(effects::EffectsTextPlugin)
+            .add_plugins(game_over::GameOverTextPlugin)
+            .add_plugins(input_prompts::PromptPlugin)
+            .add_plugins(pause_menu::PauseMenuPlugin);
     }
 }
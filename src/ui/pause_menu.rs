@@ -0,0 +1,221 @@
+//! Pauses a run with `Escape`, `P` or the start button, or when the window loses focus, and shows
+//! a menu to resume, restart or quit. The menu works with the keyboard, the mouse and gamepads.
+
+use bevy::color::palettes::css::GOLD;
+use bevy::prelude::*;
+use bevy::window::WindowFocused;
+use leafwing_input_manager::prelude::*;
+
+use crate::gameplay::{PauseState, ResetRun};
+
+#[derive(Actionlike, PartialEq, Eq, Hash, Clone, Copy, Debug, Reflect)]
+pub enum PauseAction {
+    /// Pause the run, or resume it from the menu
+    Toggle,
+    Up,
+    Down,
+    /// Choose the selected menu item
+    Confirm,
+}
+
+fn create_input_map() -> InputMap<PauseAction> {
+    let mut input_map = InputMap::default();
+
+    input_map.insert(PauseAction::Toggle, KeyCode::Escape);
+    input_map.insert(PauseAction::Toggle, KeyCode::KeyP);
+    input_map.insert(PauseAction::Toggle, GamepadButton::Start);
+    input_map.insert(PauseAction::Up, KeyCode::ArrowUp);
+    input_map.insert(PauseAction::Up, KeyCode::KeyW);
+    input_map.insert(PauseAction::Up, GamepadButton::DPadUp);
+    input_map.insert(PauseAction::Down, KeyCode::ArrowDown);
+    input_map.insert(PauseAction::Down, KeyCode::KeyS);
+    input_map.insert(PauseAction::Down, GamepadButton::DPadDown);
+    input_map.insert(PauseAction::Confirm, KeyCode::Enter);
+    input_map.insert(PauseAction::Confirm, KeyCode::Space);
+    input_map.insert(PauseAction::Confirm, GamepadButton::South);
+
+    input_map
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+enum MenuItem {
+    Resume,
+    Restart,
+    Quit,
+}
+
+impl MenuItem {
+    const ALL: [Self; 3] = [Self::Resume, Self::Restart, Self::Quit];
+
+    fn label(self) -> &'static str {
+        match self {
+            MenuItem::Resume => "Resume",
+            MenuItem::Restart => "Restart",
+            MenuItem::Quit => "Quit",
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct PauseMenu {
+    /// Index of the highlighted [`MenuItem`]
+    selected: usize,
+    /// Confirm was pressed while the menu was open
+    confirm_pressed: bool,
+    /// The item the mouse button was pressed on
+    clicked: Option<MenuItem>,
+}
+
+pub struct PauseMenuPlugin;
+
+impl Plugin for PauseMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(InputManagerPlugin::<PauseAction>::default())
+            .init_resource::<ActionState<PauseAction>>()
+            .insert_resource(create_input_map())
+            .init_resource::<PauseMenu>()
+            .add_systems(OnEnter(PauseState::Paused), open_menu)
+            .add_systems(
+                Update,
+                (
+                    (pause, pause_on_focus_loss).run_if(in_state(PauseState::Running)),
+                    (navigate, choose, highlight)
+                        .chain()
+                        .run_if(in_state(PauseState::Paused)),
+                ),
+            );
+    }
+}
+
+fn pause(
+    action_state: Res<ActionState<PauseAction>>,
+    mut next_state: ResMut<NextState<PauseState>>,
+) {
+    if action_state.just_pressed(&PauseAction::Toggle) {
+        next_state.set(PauseState::Paused);
+    }
+}
+
+fn pause_on_focus_loss(
+    mut window_focused: MessageReader<WindowFocused>,
+    mut next_state: ResMut<NextState<PauseState>>,
+) {
+    if window_focused.read().any(|event| !event.focused) {
+        next_state.set(PauseState::Paused);
+    }
+}
+
+fn open_menu(mut commands: Commands, mut menu: ResMut<PauseMenu>) {
+    *menu = PauseMenu::default();
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(20.0),
+                ..default()
+            },
+            BackgroundColor(Color::BLACK.with_alpha(0.5)),
+            // Above the score and other texts
+            GlobalZIndex(1),
+            DespawnOnExit(PauseState::Paused),
+        ))
+        .with_children(|parent| {
+            parent.spawn((Text::new("Paused"), TextFont::from_font_size(80.0)));
+
+            for item in MenuItem::ALL {
+                parent.spawn((
+                    Button,
+                    Node {
+                        width: Val::Px(300.0),
+                        padding: UiRect::all(Val::Px(10.0)),
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    item,
+                    children![(Text::new(item.label()), TextFont::from_font_size(50.0))],
+                ));
+            }
+        });
+}
+
+fn navigate(
+    action_state: Res<ActionState<PauseAction>>,
+    mut menu: ResMut<PauseMenu>,
+    item_query: Query<(&Interaction, &MenuItem), Changed<Interaction>>,
+) {
+    let count = MenuItem::ALL.len();
+
+    if action_state.just_pressed(&PauseAction::Up) {
+        menu.selected = (menu.selected + count - 1) % count;
+    }
+    if action_state.just_pressed(&PauseAction::Down) {
+        menu.selected = (menu.selected + 1) % count;
+    }
+
+    for (interaction, item) in &item_query {
+        if *interaction == Interaction::Hovered
+            && let Some(index) = MenuItem::ALL.iter().position(|other| other == item)
+        {
+            menu.selected = index;
+        }
+    }
+}
+
+// Items are chosen when the button is released rather than pressed. Otherwise the jump button,
+// which also confirms, would still be held when the run resumes and make the bird jump.
+fn choose(
+    mut commands: Commands,
+    action_state: Res<ActionState<PauseAction>>,
+    mut menu: ResMut<PauseMenu>,
+    item_query: Query<(&Interaction, &MenuItem), Changed<Interaction>>,
+    mut next_state: ResMut<NextState<PauseState>>,
+    mut app_exit: MessageWriter<AppExit>,
+) {
+    if action_state.just_pressed(&PauseAction::Toggle) {
+        next_state.set(PauseState::Running);
+        return;
+    }
+
+    // Not a jump that was still held when the game paused
+    if action_state.just_pressed(&PauseAction::Confirm) {
+        menu.confirm_pressed = true;
+    }
+    let mut chosen = (menu.confirm_pressed && action_state.just_released(&PauseAction::Confirm))
+        .then_some(MenuItem::ALL[menu.selected]);
+
+    for (interaction, item) in &item_query {
+        match interaction {
+            Interaction::Pressed => menu.clicked = Some(*item),
+            Interaction::Hovered if menu.clicked == Some(*item) => chosen = Some(*item),
+            _ => menu.clicked = None,
+        }
+    }
+
+    let Some(item) = chosen else {
+        return;
+    };
+
+    match item {
+        MenuItem::Resume => next_state.set(PauseState::Running),
+        MenuItem::Restart => commands.queue(ResetRun),
+        MenuItem::Quit => {
+            app_exit.write(AppExit::Success);
+        }
+    }
+}
+
+fn highlight(menu: Res<PauseMenu>, mut item_query: Query<(&MenuItem, &mut BackgroundColor)>) {
+    for (item, mut background) in &mut item_query {
+        let is_selected = MenuItem::ALL[menu.selected] == *item;
+        background.0 = if is_selected {
+            Color::from(GOLD).with_alpha(0.6)
+        } else {
+            Color::WHITE.with_alpha(0.1)
+        };
+    }
+}
@@ -1,4 +1,7 @@
-use crate::gameplay::{GameState, ScoreInfo, ScoredEvent};
+use crate::gameplay::{GameState, PassKind, ScoreInfo, ScoredEvent};
+use crate::player::ghost::Ghost;
+use crate::scene::SeedMode;
+use crate::scene::difficulty::Difficulty;
 use bevy::prelude::*;
 
 use bevy::color::palettes::css::GOLD;
@@ -8,29 +11,64 @@ pub struct ScoreTextPlugin;
 impl Plugin for ScoreTextPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup)
-            .add_systems(Update, update_score)
-            .add_systems(OnEnter(GameState::Ready), update_high_score);
+            .add_systems(Update, (update_score, update_ghost_score))
+            .add_systems(OnEnter(GameState::Ready), (update_high_score, hide_combo));
     }
 }
 
 #[derive(Component)]
 struct ScoreText;
 
+#[derive(Component)]
+struct GhostScoreText;
+
+/// Shows the kind of pass and the combo after a perfect pass or near miss
+#[derive(Component)]
+struct ComboText;
+
 #[derive(Component)]
 struct HighScoreText;
 
 fn setup(mut commands: Commands) {
     commands.spawn((
-        Text::new("0"),
-        TextFont::from_font_size(100.0),
-        TextColor(GOLD.into()),
-        TextLayout::new_with_justify(Justify::Center),
         Node {
             position_type: PositionType::Absolute,
             justify_self: JustifySelf::Center,
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
             ..default()
         },
-        ScoreText,
+        children![
+            (
+                Text::new("0"),
+                TextFont::from_font_size(100.0),
+                TextColor(GOLD.into()),
+                TextLayout::new_with_justify(Justify::Center),
+                ScoreText,
+            ),
+            // Placed to the right of the score without moving it off center
+            (
+                Text::new("0"),
+                TextFont::from_font_size(50.0),
+                TextColor(Color::WHITE.with_alpha(0.5)),
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(100.0),
+                    top: Val::Px(20.0),
+                    margin: UiRect::left(Val::Px(20.0)),
+                    ..default()
+                },
+                Visibility::Hidden,
+                GhostScoreText,
+            ),
+            (
+                Text::default(),
+                TextFont::from_font_size(40.0),
+                TextColor::WHITE,
+                Visibility::Hidden,
+                ComboText,
+            )
+        ],
     ));
 
     // todo: Also need to disable file creation etc
@@ -51,13 +89,49 @@ fn setup(mut commands: Commands) {
 
 fn update_score(
     score_query: Query<Entity, With<ScoreText>>,
+    mut combo_query: Query<(Entity, &mut Visibility), With<ComboText>>,
     mut scored_event: MessageReader<ScoredEvent>,
     score_info: Res<ScoreInfo>,
     mut writer: TextUiWriter,
 ) {
-    for _ in scored_event.read() {
+    for scored in scored_event.read() {
         for text_ent in &score_query {
-            *writer.text(text_ent, 0) = format!("{num}", num = score_info.current_score);
+            *writer.text(text_ent, 0) = format!("{num}", num = score_info.total_score());
+        }
+
+        for (text_ent, mut visibility) in &mut combo_query {
+            let pass = match scored.pass {
+                PassKind::Plain => {
+                    *visibility = Visibility::Hidden;
+                    continue;
+                }
+                PassKind::Perfect => "Perfect",
+                PassKind::NearMiss => "Near miss",
+            };
+
+            *writer.text(text_ent, 0) = format!("{pass} x{} +{}", scored.combo, scored.bonus);
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
+
+fn hide_combo(mut combo_query: Query<&mut Visibility, With<ComboText>>) {
+    for mut visibility in &mut combo_query {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+fn update_ghost_score(
+    mut ghost_score_query: Query<(Entity, &mut Visibility), With<GhostScoreText>>,
+    ghost: Single<&Ghost, Changed<Ghost>>,
+    mut writer: TextUiWriter,
+) {
+    for (text_ent, mut visibility) in &mut ghost_score_query {
+        if ghost.run().is_some() {
+            *writer.text(text_ent, 0) = format!("{num}", num = ghost.score);
+            *visibility = Visibility::Inherited;
+        } else {
+            *visibility = Visibility::Hidden;
         }
     }
 }
@@ -65,9 +139,17 @@ fn update_score(
 fn update_high_score(
     high_score_query: Query<Entity, With<HighScoreText>>,
     score_info: Res<ScoreInfo>,
+    seed_mode: Res<SeedMode>,
+    difficulty: Res<Difficulty>,
     mut writer: TextUiWriter,
 ) {
+    let difficulty = *difficulty;
+    let high_score = score_info.high_score_for(*seed_mode, difficulty);
+
     for text_ent in &high_score_query {
-        *writer.text(text_ent, 0) = format!("{num} ", num = score_info.high_score);
+        *writer.text(text_ent, 0) = match *seed_mode {
+            SeedMode::Daily => format!("Daily {difficulty} {high_score} "),
+            SeedMode::Random | SeedMode::Fixed(_) => format!("{difficulty} {high_score} "),
+        };
     }
 }